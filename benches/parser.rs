@@ -0,0 +1,187 @@
+//! Heap allocations and time spent parsing string-heavy and wide documents.
+//!
+//! Each string value should cost one allocation, its own buffer; anything
+//! above that is copying. Interned keys should cost one allocation per
+//! distinct key. Run with `cargo bench --bench parser`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use rs_json_parser::options::ParserOptions;
+use rs_json_parser::parser::Parser;
+
+// counts allocations, including reallocations
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+// an array of string arrays: one allocation per string value, plus the arrays
+fn document(rows: usize, cols: usize) -> (String, usize) {
+    let rows = (0..rows)
+        .map(|i| {
+            let cells = (0..cols)
+                .map(|j| format!("\" cell {} of row {} \"", j, i))
+                .collect::<Vec<_>>();
+            format!("[{}]", cells.join(", "))
+        })
+        .collect::<Vec<_>>();
+    (format!("[{}]", rows.join(",\n")), rows.len() * cols)
+}
+
+// objects with many keys each, where map growth dominates
+fn wide_objects(count: usize, keys: usize) -> String {
+    let objects = (0..count)
+        .map(|i| {
+            let members = (0..keys)
+                .map(|j| format!("\"k{}\": {}", j, i + j))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", members.join(","))
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", objects.join(","))
+}
+
+// an array of records sharing the same few keys
+fn records(count: usize) -> (String, usize) {
+    let records = (0..count)
+        .map(|i| {
+            format!(
+                "{{\"id\": {}, \"active\": true, \"score\": {}.5, \"tags\": [], \"owner\": null}}",
+                i,
+                i % 100
+            )
+        })
+        .collect::<Vec<_>>();
+    (format!("[{}]", records.join(",\n")), count * 5)
+}
+
+fn run(name: &str, input: &str, strings: usize, options: ParserOptions) {
+    let runs = 5;
+    let mut best = f64::MAX;
+    let mut allocs = 0;
+    for _ in 0..runs {
+        let input = input.to_string();
+        let before = ALLOCS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let value = Parser::with_options(input, options.clone())
+            .parse()
+            .unwrap();
+        best = best.min(start.elapsed().as_secs_f64());
+        allocs = ALLOCS.load(Ordering::Relaxed) - before;
+        black_box(value);
+    }
+
+    report(name, allocs, strings, best);
+}
+
+// like `run`, but through `parse_borrowed`, which need not copy strings
+fn run_borrowed(name: &str, input: &str, strings: usize, options: ParserOptions) {
+    let runs = 5;
+    let mut best = f64::MAX;
+    let mut allocs = 0;
+    for _ in 0..runs {
+        let before = ALLOCS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let value = Parser::borrowed(input, options.clone())
+            .parse_borrowed()
+            .unwrap();
+        best = best.min(start.elapsed().as_secs_f64());
+        allocs = ALLOCS.load(Ordering::Relaxed) - before;
+        black_box(value);
+    }
+
+    report(name, allocs, strings, best);
+}
+
+// like `run_borrowed`, but through `parse_interned`, which copies each
+// distinct key once
+fn run_interned(name: &str, input: &str, keys: usize, options: ParserOptions) {
+    let runs = 5;
+    let mut best = f64::MAX;
+    let mut allocs = 0;
+    for _ in 0..runs {
+        let before = ALLOCS.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let value = Parser::borrowed(input, options.clone())
+            .parse_interned()
+            .unwrap();
+        best = best.min(start.elapsed().as_secs_f64());
+        allocs = ALLOCS.load(Ordering::Relaxed) - before;
+        black_box(value);
+    }
+
+    report(name, allocs, keys, best);
+}
+
+fn report(name: &str, allocs: usize, strings: usize, secs: f64) {
+    println!(
+        "{}: {} allocations for {} strings ({:.2} per string), {:.1} ms",
+        name,
+        allocs,
+        strings,
+        allocs as f64 / strings as f64,
+        secs * 1e3
+    );
+}
+
+fn main() {
+    // a few wide rows, so the array buffers barely register
+    let (input, strings) = document(100, 2_000);
+    run("parse", &input, strings, ParserOptions::default());
+    run(
+        "parse, trim_strings",
+        &input,
+        strings,
+        ParserOptions {
+            trim_strings: true,
+            ..Default::default()
+        },
+    );
+
+    run_borrowed("parse_borrowed", &input, strings, ParserOptions::default());
+
+    let keys = 5_000;
+    let input = wide_objects(20, keys);
+    let strings = 20 * keys;
+    run("wide objects", &input, strings, ParserOptions::default());
+    run(
+        "wide objects, object_capacity_hint",
+        &input,
+        strings,
+        ParserOptions {
+            object_capacity_hint: Some(keys),
+            ..Default::default()
+        },
+    );
+
+    // no string values, so the keys make the difference
+    let (input, keys) = records(10_000);
+    run("records", &input, keys, ParserOptions::default());
+    run_interned(
+        "records, parse_interned",
+        &input,
+        keys,
+        ParserOptions::default(),
+    );
+}
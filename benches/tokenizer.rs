@@ -0,0 +1,79 @@
+//! Tokenizer throughput and peak memory on a generated multi-megabyte document.
+//!
+//! Run with `cargo bench --bench tokenizer`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use rs_json_parser::tokenizer::Tokenizer;
+
+// tracks live and peak heap bytes
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let live = LIVE.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(live, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn document(records: usize) -> String {
+    let mut s = "[".to_string();
+    for i in 0..records {
+        if i > 0 {
+            s.push(',');
+        }
+        s.push_str(&format!(
+            "\n  {{\"id\": {}, \"name\": \"user-{}\", \"score\": {}.{}, \"active\": {}, \"tags\": [\"a\", \"b\", null]}}",
+            i,
+            i,
+            i % 100,
+            i % 7,
+            i % 2 == 0
+        ));
+    }
+    s.push_str("\n]");
+    s
+}
+
+fn main() {
+    let input = document(50_000);
+    let len = input.len();
+    let runs = 5;
+
+    let mut best = f64::MAX;
+    let mut peak = 0;
+    for _ in 0..runs {
+        let input = input.clone();
+        let base = LIVE.load(Ordering::Relaxed);
+        PEAK.store(base, Ordering::Relaxed);
+
+        let start = Instant::now();
+        for token in Tokenizer::new(input) {
+            black_box(token.unwrap());
+        }
+        best = best.min(start.elapsed().as_secs_f64());
+        peak = PEAK.load(Ordering::Relaxed) - base;
+    }
+
+    println!(
+        "tokenize {:.1} MB: {:.1} MB/s, {:.1} MB heap on top of the input",
+        len as f64 / 1e6,
+        len as f64 / 1e6 / best,
+        peak as f64 / 1e6
+    );
+}
@@ -0,0 +1,384 @@
+// Internal binary encoding, not meant to be read by anything but `from_binary`.
+//
+// document: MAGIC, VERSION, value
+// value:    tag byte, then
+//           Null/False/True  -
+//           Number           flags byte, int (i32 LE), then frac (f32 LE),
+//                            exponent (i32 LE), raw (string) and non_finite
+//                            (f64 LE), each only if its flag is set
+//           String           string
+//           Array            count (varint), values
+//           Object           count (varint), (string, value) pairs
+// string:   byte length (varint), UTF-8 bytes
+// varint:   unsigned LEB128
+
+use std::fmt::Display;
+
+use indexmap::IndexMap;
+
+use crate::parser::JValue;
+use crate::tokenizer::Number;
+
+const MAGIC: &[u8; 2] = b"JB";
+const VERSION: u8 = 1;
+
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STRING: u8 = 4;
+const TAG_ARRAY: u8 = 5;
+const TAG_OBJECT: u8 = 6;
+
+const HAS_FRAC: u8 = 1;
+const HAS_EXPONENT: u8 = 1 << 1;
+const HAS_RAW: u8 = 1 << 2;
+const HAS_NON_FINITE: u8 = 1 << 3;
+
+// the nesting `from_binary` accepts, as `ParserOptions::max_depth` does by
+// default; decoding recurses once per level
+const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BinaryError {
+    /// Missing magic bytes or an unknown version.
+    BadHeader,
+    UnexpectedEof,
+    InvalidTag(u8, usize),
+    InvalidUtf8(usize),
+    /// A length does not fit in `usize`.
+    InvalidLength(usize),
+    TrailingBytes(usize),
+    /// Arrays and objects nest deeper than 128 levels, starting at this byte.
+    TooDeep(usize),
+}
+
+impl Display for BinaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BinaryError::BadHeader => write!(f, "not a binary JSON document"),
+            BinaryError::UnexpectedEof => write!(f, "unexpected end of input"),
+            BinaryError::InvalidTag(tag, pos) => {
+                write!(f, "invalid tag {:#04x} at byte {}", tag, pos)
+            }
+            BinaryError::InvalidUtf8(pos) => write!(f, "invalid UTF-8 in string at byte {}", pos),
+            BinaryError::InvalidLength(pos) => write!(f, "invalid length at byte {}", pos),
+            BinaryError::TrailingBytes(pos) => write!(f, "trailing bytes at byte {}", pos),
+            BinaryError::TooDeep(pos) => {
+                write!(f, "nesting exceeds {} levels at byte {}", MAX_DEPTH, pos)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+impl JValue {
+    /// Encodes the value in a compact internal format that `from_binary`
+    /// loads faster than re-parsing JSON text. Numbers keep their source
+    /// text, so the round trip is lossless.
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        write_value(&mut out, self);
+        out
+    }
+
+    /// Decodes what `to_binary` wrote. Like the parser by default, it rejects
+    /// arrays and objects nested deeper than 128 levels.
+    pub fn from_binary(bytes: &[u8]) -> Result<JValue, BinaryError> {
+        let mut r = Reader {
+            bytes,
+            pos: 0,
+            depth: 0,
+        };
+        if r.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) || r.byte().ok() != Some(VERSION) {
+            return Err(BinaryError::BadHeader);
+        }
+        let value = r.value()?;
+        if r.pos < bytes.len() {
+            return Err(BinaryError::TrailingBytes(r.pos));
+        }
+        Ok(value)
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, value: &JValue) {
+    match value {
+        JValue::Null => out.push(TAG_NULL),
+        JValue::Bool(false) => out.push(TAG_FALSE),
+        JValue::Bool(true) => out.push(TAG_TRUE),
+        JValue::Number(n) => {
+            out.push(TAG_NUMBER);
+            write_number(out, n);
+        }
+        JValue::String(s) => {
+            out.push(TAG_STRING);
+            write_str(out, s);
+        }
+        JValue::Array(arr) => {
+            out.push(TAG_ARRAY);
+            write_varint(out, arr.len());
+            for v in arr {
+                write_value(out, v);
+            }
+        }
+        JValue::Object(m) => {
+            out.push(TAG_OBJECT);
+            write_varint(out, m.len());
+            for (k, v) in m {
+                write_str(out, k);
+                write_value(out, v);
+            }
+        }
+    }
+}
+
+fn write_number(out: &mut Vec<u8>, n: &Number) {
+    let mut flags = 0;
+    if n.frac.is_some() {
+        flags |= HAS_FRAC;
+    }
+    if n.exponent.is_some() {
+        flags |= HAS_EXPONENT;
+    }
+    if n.raw.is_some() {
+        flags |= HAS_RAW;
+    }
+    if n.non_finite.is_some() {
+        flags |= HAS_NON_FINITE;
+    }
+    out.push(flags);
+    out.extend_from_slice(&n.int.to_le_bytes());
+    if let Some(fr) = n.frac {
+        out.extend_from_slice(&fr.to_le_bytes());
+    }
+    if let Some(ex) = n.exponent {
+        out.extend_from_slice(&ex.to_le_bytes());
+    }
+    if let Some(raw) = &n.raw {
+        write_str(out, raw);
+    }
+    if let Some(v) = n.non_finite {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_varint(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    // arrays and objects being read
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], BinaryError> {
+        let end = self.pos.checked_add(n).ok_or(BinaryError::UnexpectedEof)?;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or(BinaryError::UnexpectedEof)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn byte(&mut self) -> Result<u8, BinaryError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], BinaryError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn varint(&mut self) -> Result<usize, BinaryError> {
+        let start = self.pos;
+        let mut n = 0usize;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            let bits = ((b & 0x7f) as usize)
+                .checked_shl(shift)
+                .filter(|bits| bits >> shift == (b & 0x7f) as usize)
+                .ok_or(BinaryError::InvalidLength(start))?;
+            n |= bits;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+            shift += 7;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, BinaryError> {
+        let len = self.varint()?;
+        let start = self.pos;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| BinaryError::InvalidUtf8(start))
+    }
+
+    fn number(&mut self) -> Result<Number, BinaryError> {
+        let flags = self.byte()?;
+        let int = i32::from_le_bytes(self.array()?);
+        let frac = match flags & HAS_FRAC {
+            0 => None,
+            _ => Some(f32::from_le_bytes(self.array()?)),
+        };
+        let exponent = match flags & HAS_EXPONENT {
+            0 => None,
+            _ => Some(i32::from_le_bytes(self.array()?)),
+        };
+        let raw = match flags & HAS_RAW {
+            0 => None,
+            _ => Some(self.string()?),
+        };
+        let non_finite = match flags & HAS_NON_FINITE {
+            0 => None,
+            _ => Some(f64::from_le_bytes(self.array()?)),
+        };
+        Ok(Number {
+            int,
+            frac,
+            exponent,
+            raw,
+            non_finite,
+        })
+    }
+
+    fn value(&mut self) -> Result<JValue, BinaryError> {
+        let pos = self.pos;
+        match self.byte()? {
+            TAG_NULL => Ok(JValue::Null),
+            TAG_FALSE => Ok(JValue::Bool(false)),
+            TAG_TRUE => Ok(JValue::Bool(true)),
+            TAG_NUMBER => Ok(JValue::Number(self.number()?)),
+            TAG_STRING => Ok(JValue::String(self.string()?)),
+            TAG_ARRAY | TAG_OBJECT if self.depth == MAX_DEPTH => Err(BinaryError::TooDeep(pos)),
+            TAG_ARRAY => {
+                self.depth += 1;
+                let len = self.varint()?;
+                // every value takes at least one byte, so a corrupt count
+                // cannot make us allocate much more than the input size
+                let mut arr = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+                for _ in 0..len {
+                    arr.push(self.value()?);
+                }
+                self.depth -= 1;
+                Ok(JValue::Array(arr))
+            }
+            TAG_OBJECT => {
+                self.depth += 1;
+                let len = self.varint()?;
+                let mut m = IndexMap::with_capacity(len.min(self.bytes.len() - self.pos));
+                for _ in 0..len {
+                    let k = self.string()?;
+                    let v = self.value()?;
+                    m.insert(k, v);
+                }
+                self.depth -= 1;
+                Ok(JValue::Object(m))
+            }
+            tag => Err(BinaryError::InvalidTag(tag, pos)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ParserOptions;
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_round_trip() {
+        let input = "{\"null\": null, \"t\": true, \"f\": false, \"s\": \"héllo\", \
+                     \"n\": [0, -12, 1.50, -0.5, 2.5e-3, 1E+10], \"nested\": [[], {}, [{\"a\": []}]], \
+                     \"non_finite\": [NaN, Infinity, -Infinity]}";
+        let options = ParserOptions {
+            allow_non_finite: true,
+            ..Default::default()
+        };
+        let value = Parser::with_options(input.to_string(), options)
+            .parse()
+            .unwrap();
+
+        let decoded = JValue::from_binary(&value.to_binary()).unwrap();
+
+        assert_eq!(decoded, value);
+        // the source text of numbers survives, not just their value
+        assert_eq!(decoded.to_string(), value.to_string());
+    }
+
+    #[test]
+    fn test_round_trip_constructed_number() {
+        let value = JValue::Array(vec![
+            JValue::Number(Number::new(3, Some(0.25), None)),
+            JValue::Number(Number::new(-7, None, Some(2))),
+        ]);
+
+        assert_eq!(JValue::from_binary(&value.to_binary()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_long_string() {
+        let value = JValue::String("x".repeat(300));
+
+        assert_eq!(JValue::from_binary(&value.to_binary()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = JValue::Array(vec![JValue::Null, JValue::Bool(true)]).to_binary();
+
+        assert_eq!(JValue::from_binary(b"{}"), Err(BinaryError::BadHeader));
+        assert_eq!(
+            JValue::from_binary(&bytes[..bytes.len() - 1]),
+            Err(BinaryError::UnexpectedEof)
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(TAG_NULL);
+        assert_eq!(
+            JValue::from_binary(&trailing),
+            Err(BinaryError::TrailingBytes(bytes.len()))
+        );
+
+        let mut bad_tag = bytes;
+        bad_tag[5] = 0xff;
+        assert_eq!(
+            JValue::from_binary(&bad_tag),
+            Err(BinaryError::InvalidTag(0xff, 5))
+        );
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // arrays of one element each, 200,000 levels deep
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        for _ in 0..200_000 {
+            bytes.extend_from_slice(&[TAG_ARRAY, 1]);
+        }
+        bytes.push(TAG_NULL);
+
+        let error = JValue::from_binary(&bytes).unwrap_err();
+        assert_eq!(error, BinaryError::TooDeep(3 + 2 * MAX_DEPTH));
+        assert_eq!(error.to_string(), "nesting exceeds 128 levels at byte 259");
+
+        let nested = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        let value = Parser::new(nested).parse().unwrap();
+        assert_eq!(JValue::from_binary(&value.to_binary()), Ok(value));
+    }
+}
@@ -0,0 +1,131 @@
+use std::borrow::Cow;
+
+use indexmap::IndexMap;
+
+use crate::parser::JValue;
+use crate::tokenizer::Number;
+
+/// A parsed value whose strings and keys may point into the input text, as
+/// returned by `Parser::parse_borrowed`. Only strings without escapes can be
+/// borrowed; the rest are owned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BorrowedValue<'a> {
+    Null,
+    Bool(bool),
+    String(Cow<'a, str>),
+    Number(Number),
+    Array(Vec<BorrowedValue<'a>>),
+    Object(IndexMap<Cow<'a, str>, BorrowedValue<'a>>),
+}
+
+impl BorrowedValue<'_> {
+    /// Copies any borrowed strings, detaching the value from the input.
+    pub fn into_owned(self) -> JValue {
+        match self {
+            BorrowedValue::Null => JValue::Null,
+            BorrowedValue::Bool(b) => JValue::Bool(b),
+            BorrowedValue::String(s) => JValue::String(s.into_owned()),
+            BorrowedValue::Number(n) => JValue::Number(n),
+            BorrowedValue::Array(arr) => {
+                JValue::Array(arr.into_iter().map(BorrowedValue::into_owned).collect())
+            }
+            BorrowedValue::Object(m) => JValue::Object(
+                m.into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<BorrowedValue<'_>> for JValue {
+    fn from(value: BorrowedValue<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+impl From<JValue> for BorrowedValue<'_> {
+    fn from(value: JValue) -> Self {
+        match value {
+            JValue::Null => BorrowedValue::Null,
+            JValue::Bool(b) => BorrowedValue::Bool(b),
+            JValue::String(s) => BorrowedValue::String(Cow::Owned(s)),
+            JValue::Number(n) => BorrowedValue::Number(n),
+            JValue::Array(arr) => {
+                BorrowedValue::Array(arr.into_iter().map(BorrowedValue::from).collect())
+            }
+            JValue::Object(m) => BorrowedValue::Object(
+                m.into_iter()
+                    .map(|(k, v)| (Cow::Owned(k), BorrowedValue::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ParserOptions;
+    use crate::parser::Parser;
+
+    fn parse(input: &str) -> BorrowedValue<'_> {
+        Parser::borrowed(input, ParserOptions::default())
+            .parse_borrowed()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_strings_without_escapes_are_borrowed() {
+        let input = r#"{"name": "plain", "esc": "a\nb", "list": ["x", 1, null]}"#;
+        let BorrowedValue::Object(m) = parse(input) else {
+            panic!("expected an object");
+        };
+
+        assert!(m.keys().all(|k| matches!(k, Cow::Borrowed(_))));
+        assert!(matches!(
+            &m["name"],
+            BorrowedValue::String(Cow::Borrowed("plain"))
+        ));
+        assert!(matches!(&m["esc"], BorrowedValue::String(Cow::Owned(s)) if s == "a\nb"));
+        assert!(matches!(
+            &m["list"],
+            BorrowedValue::Array(arr) if matches!(arr[0], BorrowedValue::String(Cow::Borrowed("x")))
+        ));
+    }
+
+    #[test]
+    fn test_into_owned_matches_parse() {
+        let input = r#"{"a": [1, 2.5, "xA"], "b": {"c": true}, "d": "  pad  "}"#;
+        let owned = Parser::new(input.to_string()).parse().unwrap();
+
+        assert_eq!(parse(input).into_owned(), owned);
+        assert_eq!(BorrowedValue::from(owned.clone()).into_owned(), owned);
+    }
+
+    #[test]
+    fn test_trim_strings() {
+        let options = ParserOptions {
+            trim_strings: true,
+            ..Default::default()
+        };
+        let value = Parser::borrowed(r#"[" pad "]"#, options)
+            .parse_borrowed()
+            .unwrap();
+
+        assert_eq!(
+            value,
+            BorrowedValue::Array(vec![BorrowedValue::String(Cow::Borrowed("pad"))])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut parser = Parser::borrowed("[1] 2", ParserOptions::default());
+
+        assert!(parser.parse_borrowed().is_err());
+        assert!(Parser::borrowed("\"top\"", ParserOptions::default())
+            .parse_borrowed()
+            .is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use indexmap::IndexMap;
+
+use crate::parser::JValue;
+
+/// Builds a `JValue::Object` entry by entry:
+///
+/// ```
+/// use rs_json_parser::builder::{ArrayBuilder, ObjectBuilder};
+/// use rs_json_parser::parser::JValue;
+///
+/// let value = ObjectBuilder::new()
+///     .insert("name", JValue::String("bob".to_string()))
+///     .insert("tags", ArrayBuilder::new().push(JValue::Null).build())
+///     .build();
+/// assert_eq!(value.to_string(), "{\"name\":\"bob\",\"tags\":[null]}");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ObjectBuilder {
+    m: IndexMap<String, JValue>,
+}
+
+impl ObjectBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(mut self, key: impl Into<String>, value: JValue) -> Self {
+        self.m.insert(key.into(), value);
+        self
+    }
+
+    pub fn build(self) -> JValue {
+        JValue::Object(self.m)
+    }
+}
+
+/// Builds a `JValue::Array` element by element.
+#[derive(Debug, Clone, Default)]
+pub struct ArrayBuilder {
+    arr: Vec<JValue>,
+}
+
+impl ArrayBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(mut self, value: JValue) -> Self {
+        self.arr.push(value);
+        self
+    }
+
+    pub fn build(self) -> JValue {
+        JValue::Array(self.arr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Number;
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(
+            ObjectBuilder::new().build(),
+            JValue::Object(IndexMap::new())
+        );
+        assert_eq!(ArrayBuilder::new().build(), JValue::Array(vec![]));
+    }
+
+    #[test]
+    fn test_nested() {
+        let input = "{\"foo\": { \"bar\": true, \"arr\": [1, 2, 3]}}".to_string();
+        let n = |i| JValue::Number(Number::new(i, None, None));
+        let expected = ObjectBuilder::new()
+            .insert(
+                "foo",
+                ObjectBuilder::new()
+                    .insert("bar", JValue::Bool(true))
+                    .insert(
+                        "arr",
+                        ArrayBuilder::new().push(n(1)).push(n(2)).push(n(3)).build(),
+                    )
+                    .build(),
+            )
+            .build();
+
+        assert_eq!(Parser::new(input).parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_insert_overwrites() {
+        let value = ObjectBuilder::new()
+            .insert("a", JValue::Null)
+            .insert("b", JValue::Null)
+            .insert("a", JValue::Bool(false))
+            .build();
+
+        assert_eq!(value.to_string(), "{\"a\":false,\"b\":null}");
+    }
+}
@@ -0,0 +1,320 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use indexmap::IndexMap;
+
+use crate::options::ParserOptions;
+use crate::parser::{JValue, ParseError, Parser};
+#[cfg(feature = "serde_json")]
+use crate::tokenizer::Number;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionError {
+    NotAnObject,
+    /// The value under this key is not a string.
+    NonStringValue(String),
+}
+
+impl Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::NotAnObject => write!(f, "value is not an object"),
+            ConversionError::NonStringValue(k) => {
+                write!(f, "value of key {:?} is not a string", k)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+impl TryFrom<&JValue> for HashMap<String, String> {
+    type Error = ConversionError;
+
+    fn try_from(value: &JValue) -> Result<Self, Self::Error> {
+        let JValue::Object(m) = value else {
+            return Err(ConversionError::NotAnObject);
+        };
+        m.iter()
+            .map(|(k, v)| match v {
+                JValue::String(s) => Ok((k.clone(), s.clone())),
+                _ => Err(ConversionError::NonStringValue(k.clone())),
+            })
+            .collect()
+    }
+}
+
+/// Keys are sorted, since a `HashMap` has no order of its own to keep.
+impl From<HashMap<String, String>> for JValue {
+    fn from(map: HashMap<String, String>) -> Self {
+        let mut entries = map.into_iter().collect::<Vec<_>>();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        JValue::Object(
+            entries
+                .into_iter()
+                .map(|(k, v)| (k, JValue::String(v)))
+                .collect::<IndexMap<_, _>>(),
+        )
+    }
+}
+
+impl From<IndexMap<String, JValue>> for JValue {
+    fn from(map: IndexMap<String, JValue>) -> Self {
+        JValue::Object(map)
+    }
+}
+
+/// An object with the pairs in the given order. A repeated key keeps its
+/// first position and its last value.
+impl<K: Into<String>, const N: usize> From<[(K, JValue); N]> for JValue {
+    fn from(pairs: [(K, JValue); N]) -> Self {
+        JValue::Object(pairs.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+}
+
+/// Parses JSON text like `Parser::parse` under the default options, so
+/// `"{}".parse::<JValue>()` gives an empty object.
+impl FromStr for JValue {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Parser::borrowed(s, ParserOptions::default()).parse()
+    }
+}
+
+/// The same as `FromStr`, for `try_into` and generic code.
+impl TryFrom<&str> for JValue {
+    type Error = ParseError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Integers that fit `i64` or `u64` stay integers; other numbers become
+/// floats. `NaN` and the infinities have no JSON form and become `Null`.
+#[cfg(feature = "serde_json")]
+impl From<JValue> for serde_json::Value {
+    fn from(value: JValue) -> Self {
+        match value {
+            JValue::Null => serde_json::Value::Null,
+            JValue::Bool(b) => serde_json::Value::Bool(b),
+            JValue::String(s) => serde_json::Value::String(s),
+            JValue::Number(n) => number_to_serde(&n),
+            JValue::Array(arr) => arr.into_iter().map(serde_json::Value::from).collect(),
+            JValue::Object(m) => serde_json::Value::Object(
+                m.into_iter()
+                    .map(|(k, v)| (k, serde_json::Value::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Numbers keep serde_json's text for them, so they serialize the same way.
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Value> for JValue {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => JValue::Null,
+            serde_json::Value::Bool(b) => JValue::Bool(b),
+            serde_json::Value::String(s) => JValue::String(s),
+            serde_json::Value::Number(n) => JValue::Number(number_from_serde(&n)),
+            serde_json::Value::Array(arr) => {
+                JValue::Array(arr.into_iter().map(JValue::from).collect())
+            }
+            serde_json::Value::Object(m) => {
+                JValue::Object(m.into_iter().map(|(k, v)| (k, JValue::from(v))).collect())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn number_to_serde(n: &Number) -> serde_json::Value {
+    if !n.is_finite() {
+        return serde_json::Value::Null;
+    }
+    let text = n.to_string();
+    if !text.contains(['.', 'e', 'E']) {
+        if let Ok(i) = text.parse::<i64>() {
+            return i.into();
+        }
+        if let Ok(u) = text.parse::<u64>() {
+            return u.into();
+        }
+    }
+    text.parse::<f64>()
+        .ok()
+        .and_then(serde_json::Number::from_f64)
+        .map_or(serde_json::Value::Null, serde_json::Value::Number)
+}
+
+#[cfg(feature = "serde_json")]
+fn number_from_serde(n: &serde_json::Number) -> Number {
+    let text = n.to_string();
+    text.parse().unwrap_or_else(|_| {
+        // the integer part of a `Number` is an i32, so for larger values the
+        // parts come from scientific notation and the digits live in `raw`
+        let parts = format!("{:e}", n.as_f64().unwrap_or(0.0))
+            .parse()
+            .unwrap_or_else(|_| Number::new(0, None, None));
+        Number {
+            raw: Some(text),
+            ..parts
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Number;
+
+    #[test]
+    fn test_hash_map_round_trip() {
+        let value = Parser::new("{\"b\": \"2\", \"a\": \"1\"}".to_string())
+            .parse()
+            .unwrap();
+        let map = HashMap::<String, String>::try_from(&value).unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a"], "1");
+        assert_eq!(JValue::from(map).to_string(), "{\"a\":\"1\",\"b\":\"2\"}");
+    }
+
+    #[test]
+    fn test_hash_map_non_string() {
+        let value = Parser::new("{\"a\": \"1\", \"b\": 2}".to_string())
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            HashMap::<String, String>::try_from(&value),
+            Err(ConversionError::NonStringValue("b".to_string()))
+        );
+        assert_eq!(
+            HashMap::<String, String>::try_from(&JValue::Null),
+            Err(ConversionError::NotAnObject)
+        );
+    }
+
+    #[test]
+    fn test_from_pairs() {
+        let number = |n| JValue::Number(Number::new(n, None, None));
+        let value = JValue::from([
+            ("a", number(1)),
+            (
+                "b",
+                JValue::Array(vec![JValue::String("x".to_string()), JValue::Bool(true)]),
+            ),
+            ("c", JValue::from([("d", JValue::Null)])),
+        ]);
+
+        assert_eq!(
+            value.to_string(),
+            "{\"a\":1,\"b\":[\"x\",true],\"c\":{\"d\":null}}"
+        );
+        assert_eq!(
+            JValue::from([("k", number(1)), ("j", number(2)), ("k", number(3))]).to_string(),
+            "{\"k\":3,\"j\":2}"
+        );
+    }
+
+    #[test]
+    fn test_from_str() {
+        let value = "{\"a\": [1, null]}".parse::<JValue>().unwrap();
+
+        assert_eq!(value.to_string(), "{\"a\":[1,null]}");
+        assert_eq!("[1,".parse::<JValue>(), Err(ParseError::UnexpectedEof));
+        assert_eq!(JValue::from_str("[]"), Ok(JValue::Array(Vec::new())));
+    }
+
+    #[test]
+    fn test_try_from_str() -> Result<(), ParseError> {
+        let v: JValue = "{}".try_into()?;
+        assert_eq!(v, JValue::Object(IndexMap::new()));
+
+        let v = JValue::try_from("[true]")?;
+        assert_eq!(v, JValue::Array(vec![JValue::Bool(true)]));
+        assert_eq!(JValue::try_from("[1,"), Err(ParseError::UnexpectedEof));
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_index_map() {
+        let mut m = IndexMap::new();
+        m.insert("z".to_string(), JValue::String("last".to_string()));
+        m.insert("a".to_string(), JValue::String("first".to_string()));
+
+        assert_eq!(
+            JValue::from(m).to_string(),
+            "{\"z\":\"last\",\"a\":\"first\"}"
+        );
+    }
+}
+
+#[cfg(all(test, feature = "serde_json"))]
+mod tests_serde_json {
+    use super::*;
+    use crate::options::ParserOptions;
+    use crate::parser::Parser;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_serde_json() {
+        let input = "{\"id\": 7, \"neg\": -3, \"ratio\": 0.25, \"exp\": 1e3, \
+                     \"tags\": [\"a\", null, true], \"nested\": {\"z\": {}, \"a\": []}}";
+        let value = Parser::new(input.to_string()).parse().unwrap();
+        let serde_value = serde_json::Value::from(value.clone());
+
+        assert_eq!(
+            serde_value,
+            json!({
+                "id": 7, "neg": -3, "ratio": 0.25, "exp": 1000.0,
+                "tags": ["a", null, true], "nested": {"z": {}, "a": []}
+            })
+        );
+        assert!(serde_value["id"].is_i64());
+        assert!(serde_value["exp"].is_f64());
+        // key order survives both ways
+        assert_eq!(
+            serde_value.to_string(),
+            value.to_string().replace("1e3", "1000.0")
+        );
+        assert_eq!(
+            JValue::from(serde_value.clone()).to_string(),
+            serde_value.to_string()
+        );
+    }
+
+    #[test]
+    fn test_from_serde_json() {
+        let serde_value = json!({
+            "small": 1,
+            "big": 12345678901234i64,
+            "huge": u64::MAX,
+            "float": -1.5e300,
+            "list": [0.1, "x", false, null],
+        });
+        let value = JValue::from(serde_value.clone());
+
+        assert_eq!(value.to_string(), serde_value.to_string());
+        assert_eq!(serde_json::Value::from(value), serde_value);
+    }
+
+    #[test]
+    fn test_non_finite_to_serde_json() {
+        let options = ParserOptions {
+            allow_non_finite: true,
+            ..Default::default()
+        };
+        let value = Parser::with_options("[NaN, -Infinity]".to_string(), options)
+            .parse()
+            .unwrap();
+
+        assert_eq!(serde_json::Value::from(value), json!([null, null]));
+    }
+}
@@ -0,0 +1,151 @@
+use std::rc::Rc;
+
+use indexmap::IndexMap;
+
+use crate::parser::JValue;
+use crate::tokenizer::Number;
+
+/// A parsed value whose equal object keys share one allocation, as returned
+/// by `Parser::parse_interned`. Worth it for documents such as arrays of
+/// records, where the same few keys repeat throughout.
+#[derive(Debug, Clone, PartialEq)]
+pub enum InternedValue {
+    Null,
+    Bool(bool),
+    String(String),
+    Number(Number),
+    Array(Vec<InternedValue>),
+    Object(IndexMap<Rc<str>, InternedValue>),
+}
+
+impl InternedValue {
+    /// Copies every key into a `String` of its own.
+    pub fn into_owned(self) -> JValue {
+        match self {
+            InternedValue::Null => JValue::Null,
+            InternedValue::Bool(b) => JValue::Bool(b),
+            InternedValue::String(s) => JValue::String(s),
+            InternedValue::Number(n) => JValue::Number(n),
+            InternedValue::Array(arr) => {
+                JValue::Array(arr.into_iter().map(InternedValue::into_owned).collect())
+            }
+            InternedValue::Object(m) => JValue::Object(
+                m.into_iter()
+                    .map(|(k, v)| (k.to_string(), v.into_owned()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<InternedValue> for JValue {
+    fn from(value: InternedValue) -> Self {
+        value.into_owned()
+    }
+}
+
+/// Keys are not shared with anything; each gets an `Rc` of its own.
+impl From<JValue> for InternedValue {
+    fn from(value: JValue) -> Self {
+        match value {
+            JValue::Null => InternedValue::Null,
+            JValue::Bool(b) => InternedValue::Bool(b),
+            JValue::String(s) => InternedValue::String(s),
+            JValue::Number(n) => InternedValue::Number(n),
+            JValue::Array(arr) => {
+                InternedValue::Array(arr.into_iter().map(InternedValue::from).collect())
+            }
+            JValue::Object(m) => InternedValue::Object(
+                m.into_iter()
+                    .map(|(k, v)| (Rc::from(k), InternedValue::from(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::ParserOptions;
+    use crate::parser::{ParseError, Parser};
+
+    fn parse(input: &str) -> InternedValue {
+        Parser::borrowed(input, ParserOptions::default())
+            .parse_interned()
+            .unwrap()
+    }
+
+    // the key object `i` of an array of objects stores for `key`
+    fn key(value: &InternedValue, i: usize, key: &str) -> Rc<str> {
+        let InternedValue::Array(arr) = value else {
+            panic!("expected an array");
+        };
+        let InternedValue::Object(m) = &arr[i] else {
+            panic!("expected an object");
+        };
+        Rc::clone(m.get_key_value(key).unwrap().0)
+    }
+
+    #[test]
+    fn test_equal_keys_share_storage() {
+        let value = parse(r#"[{"id": 1, "tags": {"id": 2}}, {"tags": null, "id": 3}]"#);
+
+        assert!(Rc::ptr_eq(&key(&value, 0, "id"), &key(&value, 1, "id")));
+        assert!(Rc::ptr_eq(&key(&value, 0, "tags"), &key(&value, 1, "tags")));
+        // three keys and the clone `key` returned
+        assert_eq!(Rc::strong_count(&key(&value, 0, "id")), 4);
+    }
+
+    #[test]
+    fn test_into_owned_matches_parse() {
+        let input = r#"[{"a": [1, 2.5, "x"], "b": {"a": true}}, {"b": null, "a": "A"}]"#;
+        let owned = Parser::new(input.to_string()).parse().unwrap();
+
+        assert_eq!(parse(input).into_owned(), owned);
+        assert_eq!(InternedValue::from(owned.clone()).into_owned(), owned);
+    }
+
+    #[test]
+    fn test_max_interned_keys() {
+        let input = r#"[{"a": 1, "b": 2, "c": 3}, {"c": 4, "b": 5, "a": 6}]"#;
+        let options = ParserOptions {
+            max_interned_keys: Some(2),
+            ..Default::default()
+        };
+        let value = Parser::borrowed(input, options.clone())
+            .parse_interned()
+            .unwrap();
+
+        assert!(Rc::ptr_eq(&key(&value, 0, "a"), &key(&value, 1, "a")));
+        assert!(Rc::ptr_eq(&key(&value, 0, "b"), &key(&value, 1, "b")));
+        assert!(!Rc::ptr_eq(&key(&value, 0, "c"), &key(&value, 1, "c")));
+        assert_eq!(value, parse(input));
+
+        let options = ParserOptions {
+            reject_uninterned_keys: true,
+            ..options
+        };
+        let error = Parser::borrowed(input, options)
+            .parse_interned()
+            .unwrap_err();
+        assert_eq!(error, ParseError::TooManyInternedKeys(2));
+        assert_eq!(error.to_string(), "more than 2 distinct object keys");
+    }
+
+    #[test]
+    fn test_duplicate_key_strategy() {
+        let options = ParserOptions {
+            duplicate_key_strategy: crate::options::DuplicateKeyStrategy::Collect,
+            ..Default::default()
+        };
+        let value = Parser::borrowed(r#"{"a": 1, "a": 2}"#, options.clone())
+            .parse_interned()
+            .unwrap();
+        let owned = Parser::borrowed(r#"{"a": 1, "a": 2}"#, options)
+            .parse()
+            .unwrap();
+
+        assert_eq!(value.into_owned(), owned);
+    }
+}
@@ -0,0 +1,12 @@
+pub mod binary;
+pub mod borrowed;
+pub mod builder;
+pub mod convert;
+pub mod interned;
+pub mod options;
+pub mod parser;
+pub mod patch;
+pub mod serializer;
+pub mod shape;
+pub mod tokenizer;
+pub mod value;
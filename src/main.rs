@@ -1,6 +1,3 @@
-mod tokenizer;
-mod parser;
-
 fn main() {
     println!("Hello, world!");
 }
@@ -0,0 +1,126 @@
+/// What an object keeps when a key repeats within it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeyStrategy {
+    /// The last value, at the position of the first occurrence.
+    #[default]
+    LastWins,
+    FirstWins,
+    /// Fail the parse with `ParseError::DuplicateKey`.
+    Reject,
+    /// All values in an array, in document order. Keys that occur once keep
+    /// their value as is.
+    Collect,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    /// Trim leading and trailing ASCII whitespace from string values. Keys are
+    /// left as they are.
+    pub trim_strings: bool,
+    /// Wrap errors in `ParseError::Context` with the containers they occurred in.
+    pub track_context: bool,
+    /// Wrap errors in `ParseError::At` with their byte offset and the input
+    /// around it, which its `Display` shows with a caret under the offset.
+    pub error_snippets: bool,
+    /// Reject numbers that are valid JSON but not minimal, such as `1.50` or
+    /// `1e01`. See `Number::is_canonical`.
+    pub reject_noncanonical_numbers: bool,
+    /// Rewrite every number into the canonical form described on
+    /// `Number::normalized`, so `1E2` and `100` parse alike. Applied after
+    /// `reject_noncanonical_numbers` has seen the number as written.
+    pub normalize_numbers: bool,
+    /// Accept the `NaN`, `Infinity` and `-Infinity` literals emitted by some
+    /// producers, such as Python's `json.dumps`.
+    pub allow_non_finite: bool,
+    /// Upper bound on the bytes of all string contents in the document
+    /// combined, keys included.
+    pub max_total_string_bytes: Option<usize>,
+    /// Upper bound on the length of the whole input in bytes, checked before
+    /// anything is tokenized.
+    pub max_input_len: Option<usize>,
+    /// Upper bound on the bytes of a single string once unescaped, keys
+    /// included. Checked before the text is copied.
+    pub max_string_len: Option<usize>,
+    /// Accept strings delimited by `'` as well, as written in JavaScript. A `"`
+    /// inside them needs no escape, and `\'` is accepted in any string.
+    pub allow_single_quotes: bool,
+    /// Accept identifiers as object keys without quotes, as in JSON5's
+    /// `{foo: 1}`. Identifiers are ASCII letters, digits, `_` and `$`, not
+    /// starting with a digit. `null`, `true` and `false` work as keys too.
+    pub allow_unquoted_keys: bool,
+    /// Accept hexadecimal integers such as `0x1F` or `-0xff`, as in JSON5. They
+    /// take no fraction or exponent.
+    pub allow_hex_numbers: bool,
+    /// Accept a leading `+` on numbers, as in `+1.5`. The sign of an exponent
+    /// is always allowed.
+    pub allow_leading_plus: bool,
+    /// Accept bare numbers in key position, as in `{1: "a"}`, storing them
+    /// under the text of `Number::normalized`, so `{2.50: "a"}` and
+    /// `{1e2: "a"}` have the keys `"2.5"` and `"100"`.
+    pub allow_numeric_keys: bool,
+    /// Have the tokenizer emit `JToken::RawNumber` with the source text instead
+    /// of decomposing numbers. The parser decomposes them when building values.
+    pub raw_numbers: bool,
+    /// Accept `//` line comments and `/* */` block comments wherever
+    /// whitespace may appear, as in JSONC.
+    pub allow_comments: bool,
+    /// Keep the comments around each value, see `Parser::comments`. Implies
+    /// `allow_comments`.
+    pub record_comments: bool,
+    /// Called on every object key once unescaped; a key it returns `false`
+    /// for fails the parse with `ParseError::InvalidKey`.
+    pub key_validator: Option<fn(&str) -> bool>,
+    /// Remember the path of every key that repeats within its object. See
+    /// `Parser::duplicate_keys`.
+    pub record_duplicate_keys: bool,
+    /// What to keep of a repeated key. `parse_events` reports every
+    /// occurrence unless it is `Reject`.
+    pub duplicate_key_strategy: DuplicateKeyStrategy,
+    /// Capacity to preallocate for every object, saving rehashing when
+    /// objects are known to be wide. Capped by what the rest of the input
+    /// could hold, so small inputs never reserve much.
+    pub object_capacity_hint: Option<usize>,
+    /// Upper bound on how deeply arrays and objects may nest, failing the
+    /// parse with `ParseError::TooDeep` past it. Parsing never recurses, but
+    /// dropping, cloning, printing or comparing a value does, one stack frame
+    /// per level. 128 by default, as in serde_json; `None` lifts the limit.
+    pub max_depth: Option<usize>,
+    /// Upper bound on the distinct keys `Parser::parse_interned` shares
+    /// storage between, so a document of ever new keys cannot grow its table
+    /// without end. Keys first seen past it get storage of their own.
+    pub max_interned_keys: Option<usize>,
+    /// Fail the parse with `ParseError::TooManyInternedKeys` at a key past
+    /// `max_interned_keys`, rather than leaving it out of the table.
+    pub reject_uninterned_keys: bool,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        ParserOptions {
+            trim_strings: false,
+            track_context: false,
+            error_snippets: false,
+            reject_noncanonical_numbers: false,
+            normalize_numbers: false,
+            allow_non_finite: false,
+            max_total_string_bytes: None,
+            max_input_len: None,
+            max_string_len: None,
+            allow_single_quotes: false,
+            allow_unquoted_keys: false,
+            allow_hex_numbers: false,
+            allow_leading_plus: false,
+            allow_numeric_keys: false,
+            raw_numbers: false,
+            allow_comments: false,
+            record_comments: false,
+            key_validator: None,
+            record_duplicate_keys: false,
+            duplicate_key_strategy: DuplicateKeyStrategy::default(),
+            object_capacity_hint: None,
+            max_depth: Some(128),
+            max_interned_keys: None,
+            reject_uninterned_keys: false,
+        }
+    }
+}
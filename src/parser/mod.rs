@@ -1,9 +1,18 @@
-use std::iter::Peekable;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::io::{self, Read};
+use std::rc::Rc;
 
-use crate::tokenizer::{JToken, Number, Tokenizer};
+use crate::borrowed::BorrowedValue;
+use crate::interned::InternedValue;
+use crate::options::{DuplicateKeyStrategy, ParserOptions};
+use crate::tokenizer::{JToken, Lexeme, Number, TokenKind, TokenizeError, Tokenizer};
+use crate::value::escape_pointer_token;
 use indexmap::IndexMap;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum JValue {
     Null,
     Bool(bool),
@@ -13,213 +22,2456 @@ pub enum JValue {
     Object(IndexMap<String, JValue>),
 }
 
-pub struct Parser {
-    t: Peekable<Tokenizer>,
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    Tokenize(TokenizeError),
+    UnexpectedToken(JToken),
+    UnexpectedEof,
+    /// The input has no document at all, only whitespace or nothing.
+    EmptyInput,
+    ExpectedColon,
+    ExpectedValue,
+    TrailingTokens(JToken),
+    NonCanonicalNumber(Number),
+    /// A key that `ParserOptions::key_validator` rejected.
+    InvalidKey(String),
+    /// A container closed by the other kind of bracket, as in `[1}` or `{]`.
+    MismatchedCloser {
+        expected: char,
+        found: char,
+    },
+    /// Arrays and objects nest deeper than `ParserOptions::max_depth`, which
+    /// this holds.
+    TooDeep(usize),
+    /// A new key past `ParserOptions::max_interned_keys`, which this holds,
+    /// with `ParserOptions::reject_uninterned_keys`.
+    TooManyInternedKeys(usize),
+    /// A repeated key, with `DuplicateKeyStrategy::Reject`.
+    DuplicateKey(String),
+    /// Wraps another error with the containers it occurred in, outermost
+    /// first. Only produced when `ParserOptions::track_context` is set.
+    Context {
+        error: Box<ParseError>,
+        breadcrumbs: Vec<Breadcrumb>,
+    },
+    /// Wraps another error with the byte offset it occurred at and the input
+    /// around it. `caret` is the offset's position in `snippet`, in chars.
+    /// Only produced when `ParserOptions::error_snippets` is set.
+    At {
+        error: Box<ParseError>,
+        pos: usize,
+        snippet: String,
+        caret: usize,
+    },
+}
+
+impl ParseError {
+    /// The underlying error, with any context wrapper removed.
+    pub fn root(&self) -> &ParseError {
+        match self {
+            ParseError::Context { error, .. } | ParseError::At { error, .. } => error.root(),
+            e => e,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Breadcrumb {
+    Object,
+    Array,
+    Key(String),
+    Index(usize),
+}
+
+impl Display for Breadcrumb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Breadcrumb::Object => write!(f, "object"),
+            Breadcrumb::Array => write!(f, "array"),
+            Breadcrumb::Key(k) => write!(f, "object key {:?}", k),
+            Breadcrumb::Index(i) => write!(f, "array index {}", i),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Tokenize(e) => write!(f, "{}", e),
+            ParseError::UnexpectedToken(t) => write!(f, "unexpected `{}`", t),
+            ParseError::UnexpectedEof => write!(f, "unexpected end of input"),
+            ParseError::EmptyInput => {
+                write!(f, "expected a JSON object or array, but the input is empty")
+            }
+            ParseError::ExpectedColon => write!(f, "expected ':' after object key"),
+            ParseError::ExpectedValue => write!(f, "expected a value"),
+            ParseError::TrailingTokens(t) => {
+                write!(f, "unexpected `{}` after the end of the document", t)
+            }
+            ParseError::NonCanonicalNumber(n) => write!(f, "non-canonical number {}", n),
+            ParseError::InvalidKey(k) => write!(f, "object key {:?} is not allowed", k),
+            ParseError::MismatchedCloser { expected, found } => {
+                let container = if *expected == ']' { "array" } else { "object" };
+                write!(
+                    f,
+                    "expected `{}` to close the {}, found `{}`",
+                    expected, container, found
+                )
+            }
+            ParseError::TooDeep(max) => write!(f, "nesting exceeds the limit of {} levels", max),
+            ParseError::TooManyInternedKeys(max) => {
+                write!(f, "more than {} distinct object keys", max)
+            }
+            ParseError::DuplicateKey(k) => write!(f, "duplicate object key {:?}", k),
+            ParseError::Context { error, breadcrumbs } => {
+                write!(f, "{} (in ", error)?;
+                for (i, b) in breadcrumbs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " → ")?;
+                    }
+                    write!(f, "{}", b)?;
+                }
+                write!(f, ")")
+            }
+            ParseError::At {
+                error,
+                pos,
+                snippet,
+                caret,
+            } => {
+                writeln!(f, "{} at byte {}", error, pos)?;
+                writeln!(f, "  {}", snippet)?;
+                write!(f, "  {:>width$}", "^", width = caret + 1)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<TokenizeError> for ParseError {
+    fn from(e: TokenizeError) -> Self {
+        ParseError::Tokenize(e)
+    }
+}
+
+/// Why `Parser::from_reader` could not produce a parser.
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Io(e) => write!(f, "{}", e),
+            ReadError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+impl From<io::Error> for ReadError {
+    fn from(e: io::Error) -> Self {
+        ReadError::Io(e)
+    }
+}
+
+impl From<ParseError> for ReadError {
+    fn from(e: ParseError) -> Self {
+        ReadError::Parse(e)
+    }
+}
+
+/// The comments around one value, as kept by `Parser::comments`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Comments {
+    /// On the lines before the value, or before its key.
+    pub leading: Vec<String>,
+    /// After the value, on its last line.
+    pub trailing: Vec<String>,
+}
+
+/// What `Parser::parse_events` reports. Objects and arrays arrive as their
+/// start, contents and end; everything else as a `Value`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    StartObject,
+    Key(String),
+    EndObject,
+    StartArray,
+    EndArray,
+    Value(JValue),
 }
 
-enum State {
-    Key,
-    Value,
+pub struct Parser<'a> {
+    t: Tokenizer<'a>,
+    // one token of lookahead; `Some(None)` once the end of input was seen
+    peeked: Option<Option<Result<Lexeme<'a>, TokenizeError>>>,
+    // where the most recently tokenized token starts
+    token_start: usize,
+    options: ParserOptions,
+    breadcrumbs: Vec<Breadcrumb>,
+    duplicate_keys: Vec<String>,
+    string_transform: Option<Box<StringTransform>>,
+    comments: IndexMap<String, Comments>,
+    // own-line comments waiting for the value they precede
+    pending_comments: Vec<String>,
+    // the value that same-line comments trail, while one can
+    last_value: Option<String>,
 }
 
-impl Parser {
+type StringTransform = dyn Fn(&str) -> Option<JValue>;
+
+impl Parser<'static> {
     pub fn new(s: String) -> Self {
-        let t = Tokenizer::new(s).peekable();
-        Self { t }
+        Self::with_options(s, ParserOptions::default())
+    }
+
+    pub fn with_options(s: String, options: ParserOptions) -> Self {
+        let t = Tokenizer::with_options(s, options.clone());
+        Self::with_tokenizer(t, options)
     }
 
-    pub fn parse(&mut self) -> JValue {
-        let token = self.t.peek();
-        let value = match token {
-            Some(JToken::LeftBrace) => self.parse_object(),
-            Some(JToken::LeftBracket) => self.parse_array(),
-            _ => panic!(""),
+    /// Reads `reader` to the end and sets up a parser over it. The bytes must
+    /// be valid UTF-8; otherwise this fails with `TokenizeError::InvalidUtf8`
+    /// at the first bad byte. With `max_input_len` set, reading stops one byte
+    /// past the limit.
+    pub fn from_reader(mut reader: impl Read, options: ParserOptions) -> Result<Self, ReadError> {
+        let mut bytes = Vec::new();
+        match options.max_input_len {
+            Some(max) => reader.take(max as u64 + 1).read_to_end(&mut bytes)?,
+            None => reader.read_to_end(&mut bytes)?,
         };
+        if let Some(max) = options.max_input_len.filter(|&max| bytes.len() > max) {
+            return Err(ParseError::Tokenize(TokenizeError::InputTooLong(max)).into());
+        }
+        let input = String::from_utf8(bytes).map_err(|e| {
+            ParseError::Tokenize(TokenizeError::InvalidUtf8(e.utf8_error().valid_up_to()))
+        })?;
+        Ok(Self::with_options(input, options))
+    }
+}
 
-        value
+impl<'a> Parser<'a> {
+    /// Parses text the caller keeps owning, so that `parse_borrowed` can hand
+    /// out strings that point into it.
+    pub fn borrowed(s: &'a str, options: ParserOptions) -> Self {
+        let t = Tokenizer::borrowed(s, options.clone());
+        Self::with_tokenizer(t, options)
     }
 
-    pub fn parse_object(&mut self) -> JValue {
-        let token = self.t.next();
-        assert_eq!(token, Some(JToken::LeftBrace));
+    fn with_tokenizer(t: Tokenizer<'a>, options: ParserOptions) -> Self {
+        Self {
+            t,
+            peeked: None,
+            token_start: 0,
+            options,
+            breadcrumbs: Vec::new(),
+            duplicate_keys: Vec::new(),
+            string_transform: None,
+            comments: IndexMap::new(),
+            pending_comments: Vec::new(),
+            last_value: None,
+        }
+    }
 
-        let mut m = IndexMap::<String, JValue>::new();
+    /// Runs `f` on every string value (not keys) after parsing it; when it
+    /// returns a value, that value takes the string's place. Useful for
+    /// turning dates and the like into something more specific.
+    pub fn with_string_transform(mut self, f: impl Fn(&str) -> Option<JValue> + 'static) -> Self {
+        self.string_transform = Some(Box::new(f));
+        self
+    }
+
+    /// Replaces the options used from here on, e.g. between documents of a
+    /// stream. A token the parser has already looked ahead at keeps the way
+    /// it was tokenized under the old options.
+    pub fn set_options(&mut self, options: ParserOptions) {
+        self.t.set_options(options.clone());
+        self.options = options;
+    }
+
+    fn pull(&mut self) -> Option<Result<Lexeme<'a>, TokenizeError>> {
+        let next = self.t.next_lexeme()?;
+        Some(next.map(|(lexeme, span)| {
+            self.token_start = span.start;
+            lexeme
+        }))
+    }
+
+    fn peek(&mut self) -> Result<Option<TokenKind>, ParseError> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.pull());
+        }
+        match self.peeked.as_ref().and_then(Option::as_ref) {
+            Some(Ok(lexeme)) => Ok(Some(lexeme.kind())),
+            Some(Err(e)) => Err(e.clone().into()),
+            None => Ok(None),
+        }
+    }
+
+    // the error for the token `peek` just looked at, which it leaves in place
+    fn unexpected_peeked(&self) -> ParseError {
+        match self.peeked.as_ref().and_then(Option::as_ref) {
+            Some(Ok(Lexeme::Str(s))) => ParseError::UnexpectedToken(JToken::String(s.to_string())),
+            Some(Ok(Lexeme::Token(token))) => ParseError::UnexpectedToken(token.clone()),
+            Some(Err(e)) => e.clone().into(),
+            None => ParseError::UnexpectedEof,
+        }
+    }
+
+    /// Kind of the next token, without consuming it. `None` at the end of
+    /// input, and also when the next token fails to tokenize; that error is
+    /// reported by whichever parse call consumes it.
+    pub fn peek_kind(&mut self) -> Option<TokenKind> {
+        self.peek().ok().flatten()
+    }
+
+    fn next_lexeme(&mut self) -> Result<Lexeme<'a>, ParseError> {
+        let lexeme = match self.peeked.take() {
+            Some(lexeme) => lexeme,
+            None => self.pull(),
+        };
+        match lexeme {
+            Some(lexeme) => Ok(lexeme?),
+            None => Err(ParseError::UnexpectedEof),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<JToken, ParseError> {
+        self.next_lexeme().map(Lexeme::into_token)
+    }
+
+    /// Parses a single document, failing if anything other than whitespace
+    /// follows it.
+    pub fn parse(&mut self) -> Result<JValue, ParseError> {
+        let value = self.parse_document()?;
+        self.expect_end()?;
+        Ok(value)
+    }
+
+    /// Parses a single document like `parse`, but instead of building it hands
+    /// `handler` one event per container boundary, key and scalar, in document
+    /// order. Memory use does not grow with the size of the document. Events
+    /// already delivered stand even if a later part of the input is invalid.
+    pub fn parse_events(&mut self, mut handler: impl FnMut(Event)) -> Result<(), ParseError> {
+        self.document(|p| p.root(&mut Handler(&mut handler)))?;
+        self.expect_end()
+    }
+
+    /// Parses a single document like `parse`, but strings and keys without
+    /// escapes borrow from the input instead of being copied, when the parser
+    /// was made with `Parser::borrowed`.
+    pub fn parse_borrowed(&mut self) -> Result<BorrowedValue<'a>, ParseError> {
+        let mut builder = BorrowedBuilder::new(self.options.duplicate_key_strategy);
+        self.document(|p| p.root(&mut builder))?;
+        self.expect_end()?;
+        Ok(builder.done.unwrap_or(BorrowedValue::Null))
+    }
+
+    /// Parses a single document like `parse`, but equal object keys share one
+    /// allocation. With a parser made by `Parser::borrowed`, each distinct key
+    /// is copied once, however often it occurs.
+    pub fn parse_interned(&mut self) -> Result<InternedValue, ParseError> {
+        let mut builder = InternedBuilder::new(&self.options);
+        self.document(|p| p.root(&mut builder))?;
+        self.expect_end()?;
+        Ok(builder.done.unwrap_or(InternedValue::Null))
+    }
+
+    fn expect_end(&mut self) -> Result<(), ParseError> {
+        let error = match self.peek() {
+            Ok(Some(_)) => match self.unexpected_peeked() {
+                ParseError::UnexpectedToken(token) => ParseError::TrailingTokens(token),
+                e => e,
+            },
+            Ok(None) => {
+                self.flush_comments();
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        Err(self.locate(error))
+    }
+
+    /// Parses the next document and stops right after it, so the following
+    /// call picks up from there. Returns `None` once only whitespace is left.
+    pub fn parse_one(&mut self) -> Result<Option<JValue>, ParseError> {
+        match self.peek() {
+            Ok(Some(_)) => self.parse_document().map(Some),
+            Ok(None) => Ok(None),
+            Err(e) => Err(self.locate(e)),
+        }
+    }
+
+    /// Parses back-to-back documents until the input is exhausted.
+    pub fn parse_stream(&mut self) -> Result<Vec<JValue>, ParseError> {
+        let mut values = Vec::new();
+        while let Some(value) = self.parse_one()? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    /// Parses every value in `input`, separated by whitespace or nothing at
+    /// all, under the default options. Unlike `parse_stream`, values need not
+    /// be objects or arrays, so `{} [] true 3` gives four values.
+    pub fn parse_all(input: &str) -> Result<Vec<JValue>, ParseError> {
+        let mut parser = Parser::borrowed(input, ParserOptions::default());
+        let mut values = Vec::new();
         loop {
-            let next = self.t.peek().cloned();
-            if next == Some(JToken::RightBrace) {
-                self.t.next();
-                break;
-            }
-
-            let key = self.t.next().unwrap();
-            let collon = self.t.next().unwrap();
-            assert!(matches!(key, JToken::String(_)));
-            assert!(matches!(collon, JToken::Collon));
-
-            let next = self.t.peek().cloned();
-            let value = match next.unwrap() {
-                JToken::Null => {
-                    self.t.next();
-                    JValue::Null
-                }
-                JToken::Bool(b) => {
-                    self.t.next();
-                    JValue::Bool(b)
-                }
-                JToken::String(s) => {
-                    self.t.next();
-                    JValue::String(s.clone())
-                }
-                JToken::Number(n) => {
-                    self.t.next();
-                    JValue::Number(n.clone())
-                }
-                JToken::LeftBrace => self.parse_object(),
-                JToken::LeftBracket => self.parse_array(),
-                _ => panic!("invalid json."),
-            };
+            match parser.peek() {
+                Ok(Some(_)) => values.push(parser.document(Parser::parse_value)?),
+                Ok(None) => return Ok(values),
+                Err(e) => return Err(parser.locate(e)),
+            }
+        }
+    }
+
+    /// Turns the parser into an iterator yielding one value per document, as
+    /// in JSON Lines. Iteration stops after the first error.
+    pub fn into_values(self) -> Values<'a> {
+        Values {
+            parser: self,
+            done: false,
+            skip_bad_lines: false,
+        }
+    }
+
+    /// JSON Pointers of the keys that repeated within their object in the last
+    /// parsed document, in the order they were seen. Only recorded when
+    /// `ParserOptions::record_duplicate_keys` is set.
+    pub fn duplicate_keys(&self) -> &[String] {
+        &self.duplicate_keys
+    }
+
+    /// Comments of the last parsed document, by the JSON Pointer of the value
+    /// they belong to. A comment on a line of its own leads the value after
+    /// it; one following a value on the same line trails it, as do comments
+    /// before a closing bracket. Only recorded when
+    /// `ParserOptions::record_comments` is set. See
+    /// `JValue::to_string_pretty_with_comments` to write them back out.
+    pub fn comments(&self) -> &IndexMap<String, Comments> {
+        &self.comments
+    }
+
+    fn parse_document(&mut self) -> Result<JValue, ParseError> {
+        self.document(Self::parse_root)
+    }
 
-            match key {
-                JToken::String(s) => {
-                    m.insert(s, value);
+    // runs `f` over one document, wrapping its errors as the options ask
+    fn document<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        self.breadcrumbs.clear();
+        self.duplicate_keys.clear();
+        self.comments.clear();
+        self.pending_comments.clear();
+        self.last_value = None;
+        f(self)
+            .map_err(|error| {
+                if !self.options.track_context {
+                    return error;
+                }
+                ParseError::Context {
+                    error: Box::new(error),
+                    breadcrumbs: std::mem::take(&mut self.breadcrumbs),
                 }
-                _ => panic!(""),
+            })
+            .map_err(|error| self.locate(error))
+    }
+
+    // wraps `error` in `ParseError::At` if snippets are on
+    fn locate(&self, error: ParseError) -> ParseError {
+        if !self.options.error_snippets {
+            return error;
+        }
+        let input = self.t.input();
+        let pos = match error.root() {
+            ParseError::Tokenize(e) => e.pos(),
+            ParseError::UnexpectedEof | ParseError::EmptyInput => input.len(),
+            _ => self.token_start,
+        };
+        let (snippet, caret) = snippet(input, pos);
+        ParseError::At {
+            error: Box::new(error),
+            pos,
+            snippet,
+            caret,
+        }
+    }
+
+    // the breadcrumb stack is only maintained when something needs the path
+    fn tracks_path(&self) -> bool {
+        self.options.track_context
+            || self.options.record_duplicate_keys
+            || self.options.record_comments
+    }
+
+    fn enter(&mut self, b: Breadcrumb) {
+        if self.tracks_path() {
+            self.breadcrumbs.push(b);
+        }
+    }
+
+    fn replace_breadcrumb(&mut self, b: impl FnOnce() -> Breadcrumb) {
+        if self.tracks_path() {
+            if let Some(last) = self.breadcrumbs.last_mut() {
+                *last = b();
             }
+        }
+    }
+
+    fn leave(&mut self) {
+        if self.tracks_path() {
+            self.breadcrumbs.pop();
+        }
+    }
+
+    fn object_capacity(&self) -> usize {
+        let Some(hint) = self.options.object_capacity_hint else {
+            return 0;
+        };
+        // the shortest member, `"":0,`, takes five bytes
+        let rest = self.t.input().len().saturating_sub(self.token_start);
+        hint.min(rest / 5 + 1)
+    }
 
-            let next = self.t.peek().cloned();
-            if next != Some(JToken::Comma) && next != Some(JToken::RightBrace) {
-                panic!("invalid object: {:?}", next);
+    // JSON Pointer of the current value, from the breadcrumbs
+    fn pointer(&self) -> String {
+        let mut pointer = String::new();
+        for b in &self.breadcrumbs {
+            match b {
+                Breadcrumb::Key(k) => pointer.push_str(&format!("/{}", escape_pointer_token(k))),
+                Breadcrumb::Index(i) => pointer.push_str(&format!("/{}", i)),
+                Breadcrumb::Object | Breadcrumb::Array => {}
             }
-            if next == Some(JToken::Comma) {
-                self.t.next();
+        }
+        pointer
+    }
+
+    fn record_duplicate_key(&mut self, key: &str) {
+        let mut pointer = self.pointer();
+        pointer.push_str(&format!("/{}", escape_pointer_token(key)));
+        self.duplicate_keys.push(pointer);
+    }
+
+    // sorts the comments the tokenizer skipped: same-line ones trail the last
+    // value, the rest wait for the next one
+    fn collect_comments(&mut self) {
+        for comment in self.t.take_comments() {
+            match &self.last_value {
+                Some(last) if !comment.own_line => {
+                    let last = last.clone();
+                    self.comments
+                        .entry(last)
+                        .or_default()
+                        .trailing
+                        .push(comment.text);
+                }
+                _ => self.pending_comments.push(comment.text),
             }
         }
-        JValue::Object(m)
     }
 
-    pub fn parse_array(&mut self) -> JValue {
-        let mut arr = Vec::<JValue>::new();
+    // called once the first token of a value has been peeked
+    fn start_value_comments(&mut self) {
+        if !self.options.record_comments {
+            return;
+        }
+        self.collect_comments();
+        if !self.pending_comments.is_empty() {
+            let leading = std::mem::take(&mut self.pending_comments);
+            self.comments
+                .entry(self.pointer())
+                .or_default()
+                .leading
+                .extend(leading);
+        }
+        self.last_value = None;
+    }
+
+    // called once a value has been read, with the breadcrumbs still on it
+    fn end_value_comments(&mut self) {
+        if !self.options.record_comments {
+            return;
+        }
+        self.flush_comments();
+        self.last_value = Some(self.pointer());
+    }
+
+    // comments with no value after them trail the last value, or the
+    // enclosing one when there is none
+    fn flush_comments(&mut self) {
+        if !self.options.record_comments {
+            return;
+        }
+        self.collect_comments();
+        if !self.pending_comments.is_empty() {
+            let path = self.last_value.clone().unwrap_or_else(|| self.pointer());
+            let trailing = std::mem::take(&mut self.pending_comments);
+            self.comments
+                .entry(path)
+                .or_default()
+                .trailing
+                .extend(trailing);
+        }
+    }
+
+    fn parse_root(&mut self) -> Result<JValue, ParseError> {
+        let mut builder = Builder::new(self.options.duplicate_key_strategy);
+        self.root(&mut builder)?;
+        Ok(builder.done.unwrap_or(JValue::Null))
+    }
+
+    // a document's top level must be an object or an array
+    fn root(&mut self, sink: &mut impl Sink<'a>) -> Result<(), ParseError> {
+        match self.peek()? {
+            Some(TokenKind::LeftBrace | TokenKind::LeftBracket) => self.walk(sink),
+            Some(_) => Err(self.unexpected_peeked()),
+            None => Err(ParseError::EmptyInput),
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<JValue, ParseError> {
+        let mut builder = Builder::new(self.options.duplicate_key_strategy);
+        self.walk(&mut builder)?;
+        Ok(builder.done.unwrap_or(JValue::Null))
+    }
+
+    fn parse_scalar(&mut self, sink: &mut impl Sink<'a>) -> Result<(), ParseError> {
+        let value = match self.next_lexeme()? {
+            Lexeme::Str(s) => {
+                let s = if self.options.trim_strings {
+                    trim(s)
+                } else {
+                    s
+                };
+                match self.string_transform.as_ref().and_then(|f| f(&s)) {
+                    Some(value) => sink.value(value),
+                    None => sink.string(s),
+                }
+                return Ok(());
+            }
+            Lexeme::Token(JToken::RawNumber(s)) => JToken::Number(self.raw_number(&s)?),
+            Lexeme::Token(token) => token,
+        };
+
+        let value = match value {
+            JToken::Null => JValue::Null,
+            JToken::Bool(b) => JValue::Bool(b),
+            JToken::Number(n) if self.options.reject_noncanonical_numbers && !n.is_canonical() => {
+                return Err(ParseError::NonCanonicalNumber(n))
+            }
+            JToken::Number(n) if self.options.normalize_numbers => JValue::Number(n.normalized()),
+            JToken::Number(n) => JValue::Number(n),
+            _ => return Err(ParseError::ExpectedValue),
+        };
+        sink.value(value);
+        Ok(())
+    }
+
+    pub fn parse_object(&mut self) -> Result<JValue, ParseError> {
+        match self.peek()? {
+            Some(TokenKind::LeftBrace) => self.parse_value(),
+            _ => Err(ParseError::UnexpectedToken(self.next_token()?)),
+        }
+    }
 
-        let token = self.t.next();
-        assert_eq!(token, Some(JToken::LeftBracket));
+    pub fn parse_array(&mut self) -> Result<JValue, ParseError> {
+        match self.peek()? {
+            Some(TokenKind::LeftBracket) => self.parse_value(),
+            _ => Err(ParseError::UnexpectedToken(self.next_token()?)),
+        }
+    }
 
+    // reads one value into `sink`. Open containers live on an explicit stack
+    // rather than the call stack, so nesting depth is bounded only by memory.
+    fn walk(&mut self, sink: &mut impl Sink<'a>) -> Result<(), ParseError> {
+        let mut open = Vec::<Open>::new();
+        // whether the last step completed a value
+        let mut completed = false;
         loop {
-            let next = self.t.peek().cloned();
-            if next == Some(JToken::RightBracket) {
-                self.t.next();
-                break;
+            if completed {
+                match open.last() {
+                    None => return Ok(()),
+                    Some(Open::Array(_)) => {
+                        self.replace_breadcrumb(|| Breadcrumb::Array);
+                        self.end_member(TokenKind::RightBracket)?;
+                    }
+                    Some(Open::Object(_)) => {
+                        self.replace_breadcrumb(|| Breadcrumb::Object);
+                        self.end_member(TokenKind::RightBrace)?;
+                    }
+                }
+            }
+
+            // close the innermost container, or get to its next value
+            match open.last_mut() {
+                Some(Open::Array(len)) => {
+                    let index = *len;
+                    self.replace_breadcrumb(|| Breadcrumb::Index(index));
+                    match self.peek()? {
+                        Some(TokenKind::RightBracket) => {
+                            self.next_token()?;
+                            self.leave();
+                            self.end_value_comments();
+                            open.pop();
+                            sink.end_array();
+                            completed = true;
+                            continue;
+                        }
+                        Some(TokenKind::RightBrace) => {
+                            return Err(mismatched(TokenKind::RightBracket))
+                        }
+                        _ => {}
+                    }
+                    *len += 1;
+                }
+                Some(Open::Object(keys)) => {
+                    match self.peek()? {
+                        Some(TokenKind::RightBrace) => {
+                            self.next_token()?;
+                            self.leave();
+                            self.end_value_comments();
+                            open.pop();
+                            sink.end_object();
+                            completed = true;
+                            continue;
+                        }
+                        Some(TokenKind::RightBracket) => {
+                            return Err(mismatched(TokenKind::RightBrace))
+                        }
+                        _ => {}
+                    }
+                    if self.options.record_comments {
+                        // comments between the key and its value lead the value
+                        self.collect_comments();
+                        self.last_value = None;
+                    }
+                    let key = self.parse_key()?;
+                    if let Some(keys) = keys {
+                        if !keys.insert(key.to_string()) {
+                            if self.options.duplicate_key_strategy == DuplicateKeyStrategy::Reject {
+                                return Err(ParseError::DuplicateKey(key.into_owned()));
+                            }
+                            self.record_duplicate_key(&key);
+                        }
+                    }
+                    self.replace_breadcrumb(|| Breadcrumb::Key(key.to_string()));
+                    sink.key(key)?;
+                }
+                None => {}
             }
 
-            let next = self.t.peek().cloned();
-            let value = match next.unwrap() {
-                JToken::Null => {
-                    self.t.next();
-                    JValue::Null
+            let kind = self.peek()?;
+            if let Some(max) = self.options.max_depth {
+                let opens = matches!(kind, Some(TokenKind::LeftBrace | TokenKind::LeftBracket));
+                if opens && open.len() >= max {
+                    return Err(ParseError::TooDeep(max));
+                }
+            }
+            self.start_value_comments();
+            match kind {
+                Some(TokenKind::LeftBrace) => {
+                    let capacity = self.object_capacity();
+                    self.next_token()?;
+                    self.enter(Breadcrumb::Object);
+                    let keys = (self.options.record_duplicate_keys
+                        || self.options.duplicate_key_strategy == DuplicateKeyStrategy::Reject)
+                        .then(HashSet::new);
+                    open.push(Open::Object(keys));
+                    sink.start_object(capacity);
+                    completed = false;
                 }
-                JToken::Bool(b) => {
-                    self.t.next();
-                    JValue::Bool(b)
+                Some(TokenKind::LeftBracket) => {
+                    self.next_token()?;
+                    self.enter(Breadcrumb::Array);
+                    open.push(Open::Array(0));
+                    sink.start_array();
+                    completed = false;
                 }
-                JToken::String(s) => {
-                    self.t.next();
-                    JValue::String(s.clone())
+                // a stray closer where a document should start, as `root` reports it
+                Some(TokenKind::RightBrace | TokenKind::RightBracket) if open.is_empty() => {
+                    return Err(self.unexpected_peeked())
                 }
-                JToken::Number(n) => {
-                    self.t.next();
-                    JValue::Number(n.clone())
+                Some(_) => {
+                    self.parse_scalar(sink)?;
+                    self.end_value_comments();
+                    completed = true;
                 }
-                JToken::LeftBrace => self.parse_object(),
-                JToken::LeftBracket => self.parse_array(),
-                _ => panic!("invalid json."),
-            };
+                None => return Err(ParseError::UnexpectedEof),
+            }
+        }
+    }
 
-            arr.push(value);
+    // decomposes the `JToken::RawNumber` just read; an error points at its
+    // start in the document, as it would without `raw_numbers`
+    fn raw_number(&self, s: &str) -> Result<Number, TokenizeError> {
+        s.parse()
+            .map_err(|_| TokenizeError::InvalidNumber(self.token_start))
+    }
 
-            let next = self.t.peek().cloned();
-            if next != Some(JToken::Comma) && next != Some(JToken::RightBracket) {
-                panic!("invalid array.")
+    // an object key and the colon after it
+    fn parse_key(&mut self) -> Result<Cow<'a, str>, ParseError> {
+        let key = match self.next_lexeme()? {
+            Lexeme::Str(s) => s,
+            Lexeme::Token(token) => Cow::Owned(match token {
+                JToken::Number(n) if self.options.allow_numeric_keys => n.normalized().to_string(),
+                JToken::RawNumber(s) if self.options.allow_numeric_keys => {
+                    self.raw_number(&s)?.normalized().to_string()
+                }
+                JToken::Ident(s) => s,
+                JToken::Null if self.options.allow_unquoted_keys => "null".to_string(),
+                JToken::Bool(b) if self.options.allow_unquoted_keys => b.to_string(),
+                token => return Err(ParseError::UnexpectedToken(token)),
+            }),
+        };
+        if let Some(valid) = self.options.key_validator {
+            if !valid(&key) {
+                return Err(ParseError::InvalidKey(key.into_owned()));
+            }
+        }
+        match self.peek()? {
+            Some(TokenKind::Collon) => {
+                self.next_token()?;
             }
-            if next == Some(JToken::Comma) {
-                self.t.next();
+            Some(_) => return Err(ParseError::ExpectedColon),
+            None => return Err(ParseError::UnexpectedEof),
+        }
+        Ok(key)
+    }
+
+    // the comma after a member, or the container's closing token, which is
+    // left for the loop to consume
+    fn end_member(&mut self, close: TokenKind) -> Result<(), ParseError> {
+        match self.peek()? {
+            Some(TokenKind::Comma) => {
+                self.next_token()?;
+                Ok(())
             }
+            Some(kind) if kind == close => Ok(()),
+            Some(TokenKind::RightBrace | TokenKind::RightBracket) => Err(mismatched(close)),
+            Some(_) => Err(self.unexpected_peeked()),
+            None => Err(ParseError::UnexpectedEof),
         }
-        JValue::Array(arr)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+// the error for a container that should end with `close` meeting the other
+// closing bracket
+fn mismatched(close: TokenKind) -> ParseError {
+    let (expected, found) = match close {
+        TokenKind::RightBracket => (']', '}'),
+        _ => ('}', ']'),
+    };
+    ParseError::MismatchedCloser { expected, found }
+}
 
-    #[test]
-    fn test_empty_object() {
-        let input = "{}".to_string();
-        let mut parser = Parser::new(input);
-        let m = IndexMap::<String, JValue>::new();
-        let expected = JValue::Object(m);
+// a container `walk` is inside of: arrays with the number of values begun so
+// far, objects with their keys when duplicates are being looked for
+enum Open {
+    Array(usize),
+    Object(Option<HashSet<String>>),
+}
 
-        assert_eq!(parser.parse(), expected);
-    }
+// receives what `walk` reads, in document order; strings separately from
+// other scalars, as they may borrow from the input
+trait Sink<'a> {
+    fn start_object(&mut self, capacity: usize);
+    fn start_array(&mut self);
+    fn key(&mut self, key: Cow<'a, str>) -> Result<(), ParseError>;
+    fn string(&mut self, s: Cow<'a, str>);
+    fn value(&mut self, value: JValue);
+    fn end_object(&mut self);
+    fn end_array(&mut self);
+}
 
-    #[test]
-    fn test_empty_array() {
-        let input = "[]".to_string();
-        let mut parser = Parser::new(input);
-        let arr = Vec::<JValue>::new();
-        let expected = JValue::Array(arr);
+// a value the builders can gather repeated members into, for
+// `DuplicateKeyStrategy::Collect`
+trait Member: Sized {
+    fn array(values: Vec<Self>) -> Self;
+    fn as_array_mut(&mut self) -> Option<&mut Vec<Self>>;
+}
 
-        assert_eq!(parser.parse(), expected);
+impl Member for JValue {
+    fn array(values: Vec<Self>) -> Self {
+        JValue::Array(values)
     }
 
-    #[test]
-    fn test_object() {
-        let input = "{\"foo\": \"bar\"}".to_string();
-        let mut parser = Parser::new(input);
-        let mut m = IndexMap::<String, JValue>::new();
-        m.insert("foo".to_string(), JValue::String("bar".to_string()));
-        let expected = JValue::Object(m);
+    fn as_array_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match self {
+            JValue::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+}
 
-        assert_eq!(parser.parse(), expected);
+impl Member for BorrowedValue<'_> {
+    fn array(values: Vec<Self>) -> Self {
+        BorrowedValue::Array(values)
     }
 
-    #[test]
-    fn test_object_with_multiple_keys() {
-        let input = "{\"foo\": \"bar\", \"active\": true, \"arr\": [1, 2, 3]}".to_string();
-        let mut parser = Parser::new(input);
-        let mut m = IndexMap::<String, JValue>::new();
-        m.insert("foo".to_string(), JValue::String("bar".to_string()));
-        m.insert("active".to_string(), JValue::Bool(true));
-        m.insert(
-            "arr".to_string(),
-            JValue::Array(vec![
-                JValue::Number(Number::new(1, None, None)),
-                JValue::Number(Number::new(2, None, None)),
-                JValue::Number(Number::new(3, None, None)),
-            ]),
-        );
-        let expected = JValue::Object(m);
+    fn as_array_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match self {
+            BorrowedValue::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+}
 
-        assert_eq!(parser.parse(), expected);
+impl Member for InternedValue {
+    fn array(values: Vec<Self>) -> Self {
+        InternedValue::Array(values)
     }
 
-    #[test]
-    fn test_nested_object() {
-        let input = "{\"foo\": { \"bar\": true, \"arr\": [1, 2, 3]}}".to_string();
-        let mut parser = Parser::new(input);
-        let mut m = IndexMap::<String, JValue>::new();
-        let mut mm = IndexMap::<String, JValue>::new();
-        mm.insert("bar".to_string(), JValue::Bool(true));
-        mm.insert(
-            "arr".to_string(),
-            JValue::Array(vec![
-                JValue::Number(Number::new(1, None, None)),
-                JValue::Number(Number::new(2, None, None)),
-                JValue::Number(Number::new(3, None, None)),
-            ]),
-        );
-        m.insert("foo".to_string(), JValue::Object(mm));
-        let expected = JValue::Object(m);
+    fn as_array_mut(&mut self) -> Option<&mut Vec<Self>> {
+        match self {
+            InternedValue::Array(arr) => Some(arr),
+            _ => None,
+        }
+    }
+}
 
-        assert_eq!(parser.parse(), expected);
+// adds a member to an object under construction; `collected` holds the
+// indices of members already gathered into an array. `Reject` is handled by
+// `walk` before the value is read.
+fn insert_member<K: Hash + Eq, V: Member>(
+    m: &mut IndexMap<K, V>,
+    collected: &mut HashSet<usize>,
+    strategy: DuplicateKeyStrategy,
+    key: K,
+    value: V,
+) {
+    match (strategy, m.get_full_mut(&key)) {
+        (DuplicateKeyStrategy::FirstWins, Some(_)) => {}
+        (DuplicateKeyStrategy::Collect, Some((i, _, existing))) => {
+            if collected.insert(i) {
+                let first = std::mem::replace(existing, V::array(Vec::new()));
+                *existing = V::array(vec![first, value]);
+            } else if let Some(arr) = existing.as_array_mut() {
+                arr.push(value);
+            }
+        }
+        _ => {
+            m.insert(key, value);
+        }
+    }
+}
+
+// an unfinished container: objects with their pending key and the members
+// collected so far
+type Frame<V, K> = (V, Option<K>, HashSet<usize>);
+
+// builds the values `walk` reads
+#[derive(Default)]
+struct Builder {
+    // unfinished containers, innermost last
+    stack: Vec<Frame<JValue, String>>,
+    done: Option<JValue>,
+    duplicates: DuplicateKeyStrategy,
+}
+
+impl Builder {
+    fn new(duplicates: DuplicateKeyStrategy) -> Self {
+        Builder {
+            duplicates,
+            ..Default::default()
+        }
+    }
+
+    fn end(&mut self) {
+        if let Some((container, _, _)) = self.stack.pop() {
+            self.value(container);
+        }
+    }
+}
+
+impl Sink<'_> for Builder {
+    fn start_object(&mut self, capacity: usize) {
+        let m = IndexMap::with_capacity(capacity);
+        self.stack.push((JValue::Object(m), None, HashSet::new()));
+    }
+
+    fn start_array(&mut self) {
+        self.stack
+            .push((JValue::Array(Vec::new()), None, HashSet::new()));
+    }
+
+    fn key(&mut self, key: Cow<'_, str>) -> Result<(), ParseError> {
+        if let Some((_, pending, _)) = self.stack.last_mut() {
+            *pending = Some(key.into_owned());
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, s: Cow<'_, str>) {
+        self.value(JValue::String(s.into_owned()));
+    }
+
+    fn value(&mut self, value: JValue) {
+        match self.stack.last_mut() {
+            Some((JValue::Array(arr), _, _)) => arr.push(value),
+            Some((JValue::Object(m), key, collected)) => {
+                let key = key.take().unwrap_or_default();
+                insert_member(m, collected, self.duplicates, key, value);
+            }
+            _ => self.done = Some(value),
+        }
+    }
+
+    fn end_object(&mut self) {
+        self.end();
+    }
+
+    fn end_array(&mut self) {
+        self.end();
+    }
+}
+
+// builds the values `walk` reads for `parse_borrowed`, keeping strings as
+// they come
+#[derive(Default)]
+struct BorrowedBuilder<'a> {
+    stack: Vec<Frame<BorrowedValue<'a>, Cow<'a, str>>>,
+    done: Option<BorrowedValue<'a>>,
+    duplicates: DuplicateKeyStrategy,
+}
+
+impl<'a> BorrowedBuilder<'a> {
+    fn new(duplicates: DuplicateKeyStrategy) -> Self {
+        BorrowedBuilder {
+            duplicates,
+            ..Default::default()
+        }
+    }
+
+    fn push(&mut self, value: BorrowedValue<'a>) {
+        match self.stack.last_mut() {
+            Some((BorrowedValue::Array(arr), _, _)) => arr.push(value),
+            Some((BorrowedValue::Object(m), key, collected)) => {
+                let key = key.take().unwrap_or_default();
+                insert_member(m, collected, self.duplicates, key, value);
+            }
+            _ => self.done = Some(value),
+        }
+    }
+
+    fn end(&mut self) {
+        if let Some((container, _, _)) = self.stack.pop() {
+            self.push(container);
+        }
+    }
+}
+
+impl<'a> Sink<'a> for BorrowedBuilder<'a> {
+    fn start_object(&mut self, capacity: usize) {
+        let m = IndexMap::with_capacity(capacity);
+        self.stack
+            .push((BorrowedValue::Object(m), None, HashSet::new()));
+    }
+
+    fn start_array(&mut self) {
+        self.stack
+            .push((BorrowedValue::Array(Vec::new()), None, HashSet::new()));
+    }
+
+    fn key(&mut self, key: Cow<'a, str>) -> Result<(), ParseError> {
+        if let Some((_, pending, _)) = self.stack.last_mut() {
+            *pending = Some(key);
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, s: Cow<'a, str>) {
+        self.push(BorrowedValue::String(s));
+    }
+
+    fn value(&mut self, value: JValue) {
+        self.push(BorrowedValue::from(value));
+    }
+
+    fn end_object(&mut self) {
+        self.end();
+    }
+
+    fn end_array(&mut self) {
+        self.end();
+    }
+}
+
+// builds the values `walk` reads for `parse_interned`, handing out one `Rc`
+// per distinct key
+#[derive(Default)]
+struct InternedBuilder {
+    stack: Vec<Frame<InternedValue, Rc<str>>>,
+    done: Option<InternedValue>,
+    duplicates: DuplicateKeyStrategy,
+    keys: HashSet<Rc<str>>,
+    max_keys: Option<usize>,
+    reject_uninterned: bool,
+}
+
+impl InternedBuilder {
+    fn new(options: &ParserOptions) -> Self {
+        InternedBuilder {
+            duplicates: options.duplicate_key_strategy,
+            max_keys: options.max_interned_keys,
+            reject_uninterned: options.reject_uninterned_keys,
+            ..Default::default()
+        }
+    }
+
+    fn intern(&mut self, key: &str) -> Result<Rc<str>, ParseError> {
+        if let Some(interned) = self.keys.get(key) {
+            return Ok(Rc::clone(interned));
+        }
+        let interned = Rc::<str>::from(key);
+        match self.max_keys {
+            Some(max) if self.keys.len() >= max && self.reject_uninterned => {
+                return Err(ParseError::TooManyInternedKeys(max))
+            }
+            Some(max) if self.keys.len() >= max => {}
+            _ => {
+                self.keys.insert(Rc::clone(&interned));
+            }
+        }
+        Ok(interned)
+    }
+
+    fn push(&mut self, value: InternedValue) {
+        match self.stack.last_mut() {
+            Some((InternedValue::Array(arr), _, _)) => arr.push(value),
+            Some((InternedValue::Object(m), key, collected)) => {
+                let key = key.take().unwrap_or_else(|| Rc::from(""));
+                insert_member(m, collected, self.duplicates, key, value);
+            }
+            _ => self.done = Some(value),
+        }
+    }
+
+    fn end(&mut self) {
+        if let Some((container, _, _)) = self.stack.pop() {
+            self.push(container);
+        }
+    }
+}
+
+impl Sink<'_> for InternedBuilder {
+    fn start_object(&mut self, capacity: usize) {
+        let m = IndexMap::with_capacity(capacity);
+        self.stack
+            .push((InternedValue::Object(m), None, HashSet::new()));
+    }
+
+    fn start_array(&mut self) {
+        self.stack
+            .push((InternedValue::Array(Vec::new()), None, HashSet::new()));
+    }
+
+    fn key(&mut self, key: Cow<'_, str>) -> Result<(), ParseError> {
+        let key = self.intern(&key)?;
+        if let Some((_, pending, _)) = self.stack.last_mut() {
+            *pending = Some(key);
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, s: Cow<'_, str>) {
+        self.push(InternedValue::String(s.into_owned()));
+    }
+
+    fn value(&mut self, value: JValue) {
+        self.push(InternedValue::from(value));
+    }
+
+    fn end_object(&mut self) {
+        self.end();
+    }
+
+    fn end_array(&mut self) {
+        self.end();
+    }
+}
+
+// forwards what `walk` reads to a `parse_events` handler
+struct Handler<F>(F);
+
+impl<F: FnMut(Event)> Sink<'_> for Handler<F> {
+    fn start_object(&mut self, _capacity: usize) {
+        (self.0)(Event::StartObject);
+    }
+
+    fn start_array(&mut self) {
+        (self.0)(Event::StartArray);
+    }
+
+    fn key(&mut self, key: Cow<'_, str>) -> Result<(), ParseError> {
+        (self.0)(Event::Key(key.into_owned()));
+        Ok(())
+    }
+
+    fn string(&mut self, s: Cow<'_, str>) {
+        (self.0)(Event::Value(JValue::String(s.into_owned())));
+    }
+
+    fn value(&mut self, value: JValue) {
+        (self.0)(Event::Value(value));
+    }
+
+    fn end_object(&mut self) {
+        (self.0)(Event::EndObject);
+    }
+
+    fn end_array(&mut self) {
+        (self.0)(Event::EndArray);
+    }
+}
+
+pub struct Values<'a> {
+    parser: Parser<'a>,
+    done: bool,
+    skip_bad_lines: bool,
+}
+
+impl Values<'_> {
+    /// See `Parser::set_options`; applies to the documents still to come.
+    pub fn set_options(&mut self, options: ParserOptions) {
+        self.parser.set_options(options);
+    }
+
+    /// Keeps iterating after an error instead of stopping: the failed
+    /// document's error is yielded, and parsing resumes on the line after the
+    /// one that document started on. Meant for JSON Lines, where each line is
+    /// a record of its own.
+    pub fn set_skip_bad_lines(&mut self, skip: bool) {
+        self.skip_bad_lines = skip;
+    }
+}
+
+impl Iterator for Values<'_> {
+    type Item = Result<JValue, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        // where the document starts, to find its line should it fail
+        let start = match self.parser.peek() {
+            Err(ParseError::Tokenize(e)) => e.pos(),
+            _ => self.parser.token_start,
+        };
+        let result = self.parser.parse_one().transpose();
+        match result {
+            Some(Err(_)) if self.skip_bad_lines => {
+                self.parser.peeked = None;
+                self.parser.t.skip_line(start);
+            }
+            Some(Ok(_)) => {}
+            _ => self.done = true,
+        }
+        result
+    }
+}
+
+// up to a few chars either side of `pos`, and how many come before it;
+// line breaks and tabs become spaces so the caret lines up
+fn snippet(input: &str, pos: usize) -> (String, usize) {
+    const RADIUS: usize = 16;
+    let mut pos = pos.min(input.len());
+    while !input.is_char_boundary(pos) {
+        pos -= 1;
+    }
+    let printable = |c: char| if c.is_control() { ' ' } else { c };
+
+    let mut before = input[..pos]
+        .chars()
+        .rev()
+        .take(RADIUS)
+        .map(printable)
+        .collect::<Vec<_>>();
+    before.reverse();
+    let caret = before.len();
+    let snippet = before
+        .into_iter()
+        .chain(input[pos..].chars().take(RADIUS).map(printable))
+        .collect();
+    (snippet, caret)
+}
+
+fn trim(s: Cow<'_, str>) -> Cow<'_, str> {
+    match s {
+        Cow::Borrowed(s) => Cow::Borrowed(s.trim_matches(|c: char| c.is_ascii_whitespace())),
+        Cow::Owned(mut s) => {
+            trim_in_place(&mut s);
+            Cow::Owned(s)
+        }
+    }
+}
+
+// trims ASCII whitespace without allocating a new string
+fn trim_in_place(s: &mut String) {
+    let end = s.trim_end_matches(|c: char| c.is_ascii_whitespace()).len();
+    s.truncate(end);
+    let start = s.len()
+        - s.trim_start_matches(|c: char| c.is_ascii_whitespace())
+            .len();
+    s.drain(..start);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_object() {
+        let input = "{}".to_string();
+        let mut parser = Parser::new(input);
+        let m = IndexMap::<String, JValue>::new();
+        let expected = JValue::Object(m);
+
+        assert_eq!(parser.parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_empty_array() {
+        let input = "[]".to_string();
+        let mut parser = Parser::new(input);
+        let arr = Vec::<JValue>::new();
+        let expected = JValue::Array(arr);
+
+        assert_eq!(parser.parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_object() {
+        let input = "{\"foo\": \"bar\"}".to_string();
+        let mut parser = Parser::new(input);
+        let mut m = IndexMap::<String, JValue>::new();
+        m.insert("foo".to_string(), JValue::String("bar".to_string()));
+        let expected = JValue::Object(m);
+
+        assert_eq!(parser.parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_object_with_multiple_keys() {
+        let input = "{\"foo\": \"bar\", \"active\": true, \"arr\": [1, 2, 3]}".to_string();
+        let mut parser = Parser::new(input);
+        let mut m = IndexMap::<String, JValue>::new();
+        m.insert("foo".to_string(), JValue::String("bar".to_string()));
+        m.insert("active".to_string(), JValue::Bool(true));
+        m.insert(
+            "arr".to_string(),
+            JValue::Array(vec![
+                JValue::Number(Number::new(1, None, None)),
+                JValue::Number(Number::new(2, None, None)),
+                JValue::Number(Number::new(3, None, None)),
+            ]),
+        );
+        let expected = JValue::Object(m);
+
+        assert_eq!(parser.parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_nested_object() {
+        let input = "{\"foo\": { \"bar\": true, \"arr\": [1, 2, 3]}}".to_string();
+        let mut parser = Parser::new(input);
+        let mut m = IndexMap::<String, JValue>::new();
+        let mut mm = IndexMap::<String, JValue>::new();
+        mm.insert("bar".to_string(), JValue::Bool(true));
+        mm.insert(
+            "arr".to_string(),
+            JValue::Array(vec![
+                JValue::Number(Number::new(1, None, None)),
+                JValue::Number(Number::new(2, None, None)),
+                JValue::Number(Number::new(3, None, None)),
+            ]),
+        );
+        m.insert("foo".to_string(), JValue::Object(mm));
+        let expected = JValue::Object(m);
+
+        assert_eq!(parser.parse(), Ok(expected));
+    }
+
+    #[test]
+    fn test_tokenize_error() {
+        let input = "[1, -]".to_string();
+        let mut parser = Parser::new(input);
+
+        assert_eq!(
+            parser.parse(),
+            Err(ParseError::Tokenize(TokenizeError::InvalidNumber(4)))
+        );
+    }
+
+    #[test]
+    fn test_missing_colon() {
+        let input = "{\"a\"}".to_string();
+        let mut parser = Parser::new(input);
+
+        assert_eq!(parser.parse(), Err(ParseError::ExpectedColon));
+    }
+
+    #[test]
+    fn test_missing_value() {
+        let input = "{\"a\":}".to_string();
+        let mut parser = Parser::new(input);
+
+        assert_eq!(parser.parse(), Err(ParseError::ExpectedValue));
+    }
+
+    #[test]
+    fn test_trim_strings() {
+        let input = "{\"name\": \"  bob  \", \" key \": [\" x \"]}".to_string();
+        let options = ParserOptions {
+            trim_strings: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(input.clone(), options);
+        let mut m = IndexMap::<String, JValue>::new();
+        m.insert("name".to_string(), JValue::String("bob".to_string()));
+        m.insert(
+            " key ".to_string(),
+            JValue::Array(vec![JValue::String("x".to_string())]),
+        );
+
+        assert_eq!(parser.parse(), Ok(JValue::Object(m)));
+
+        let mut parser = Parser::new(input);
+        let value = parser.parse().unwrap();
+        assert_eq!(
+            value.to_string(),
+            "{\"name\":\"  bob  \",\" key \":[\" x \"]}"
+        );
+    }
+
+    #[test]
+    fn test_trim_in_place() {
+        for (input, expected) in [
+            ("", ""),
+            ("   ", ""),
+            (" \té x\n", "é x"),
+            ("\u{a0}x", "\u{a0}x"),
+        ] {
+            let mut s = input.to_string();
+            trim_in_place(&mut s);
+            assert_eq!(s, expected);
+        }
+    }
+
+    #[test]
+    fn test_string_transform() {
+        let input = "{\"name\": \"bob\", \"tags\": [\"a\", 1, \"b\"]}".to_string();
+        let mut parser =
+            Parser::new(input).with_string_transform(|s| Some(JValue::String(s.to_uppercase())));
+
+        assert_eq!(
+            parser.parse().unwrap().to_string(),
+            "{\"name\":\"BOB\",\"tags\":[\"A\",1,\"B\"]}"
+        );
+
+        // `None` keeps the string as it was
+        let input = "[\"2024-01-02\", \"later\"]".to_string();
+        let mut parser = Parser::new(input).with_string_transform(|s| {
+            s.split('-')
+                .map(str::parse::<i32>)
+                .collect::<Result<Vec<_>, _>>()
+                .ok()
+                .map(|parts| {
+                    let parts = parts.into_iter().map(|n| Number::new(n, None, None));
+                    JValue::Array(parts.map(JValue::Number).collect())
+                })
+        });
+
+        assert_eq!(
+            parser.parse().unwrap().to_string(),
+            "[[2024,1,2],\"later\"]"
+        );
+    }
+
+    #[test]
+    fn test_trailing_tokens() {
+        let input = "{} {}".to_string();
+        let mut parser = Parser::new(input);
+
+        assert_eq!(
+            parser.parse(),
+            Err(ParseError::TrailingTokens(JToken::LeftBrace))
+        );
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let input = "[1] {\"a\": null}".to_string();
+        let mut parser = Parser::new(input);
+        let mut m = IndexMap::<String, JValue>::new();
+        m.insert("a".to_string(), JValue::Null);
+        let expected = vec![
+            JValue::Array(vec![JValue::Number(Number::new(1, None, None))]),
+            JValue::Object(m),
+        ];
+
+        assert_eq!(parser.parse_stream(), Ok(expected));
+    }
+
+    #[test]
+    fn test_into_values() {
+        let input = "{\"id\": 1}\n{\"id\": 2}\n{\"id\": 3}\n".to_string();
+        let parser = Parser::new(input);
+        let values = parser.into_values().collect::<Vec<_>>();
+
+        assert_eq!(values.len(), 3);
+        for (i, v) in values.into_iter().enumerate() {
+            let mut m = IndexMap::<String, JValue>::new();
+            m.insert(
+                "id".to_string(),
+                JValue::Number(Number::new(i as i32 + 1, None, None)),
+            );
+            assert_eq!(v, Ok(JValue::Object(m)));
+        }
+    }
+
+    #[test]
+    fn test_into_values_stops_on_error() {
+        let input = "[1]\n[2,\n[3]".to_string();
+        let mut values = Parser::new(input).into_values();
+
+        assert!(matches!(values.next(), Some(Ok(_))));
+        assert!(matches!(values.next(), Some(Err(_))));
+        assert!(values.next().is_none());
+    }
+
+    #[test]
+    fn test_into_values_skip_bad_lines() {
+        let input = "{\"a\": 1}\n{\"b\": \n{\"c\": 3}\n[x]\n\n[4] [5]\n[6".to_string();
+        let mut values = Parser::new(input).into_values();
+        values.set_skip_bad_lines(true);
+        let values = values
+            .map(|v| v.map(|v| v.to_string()).map_err(|_| ()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            values,
+            [
+                Ok("{\"a\":1}".to_string()),
+                // `{"c": 3}` goes to `b`, then the input ends
+                Err(()),
+                Ok("{\"c\":3}".to_string()),
+                Err(()),
+                Ok("[4]".to_string()),
+                Ok("[5]".to_string()),
+                Err(()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_error_breadcrumbs() {
+        let input = "{\"users\": [{}, {}, {}, {\"name\": \"x\", \"tags\" [1]}]}".to_string();
+        let options = ParserOptions {
+            track_context: true,
+            ..Default::default()
+        };
+        let err = Parser::with_options(input.clone(), options)
+            .parse()
+            .unwrap_err();
+
+        assert_eq!(
+            err,
+            ParseError::Context {
+                error: Box::new(ParseError::ExpectedColon),
+                breadcrumbs: vec![
+                    Breadcrumb::Key("users".to_string()),
+                    Breadcrumb::Index(3),
+                    Breadcrumb::Object,
+                ],
+            }
+        );
+        assert_eq!(err.root(), &ParseError::ExpectedColon);
+        assert_eq!(
+            err.to_string(),
+            "expected ':' after object key (in object key \"users\" → array index 3 → object)"
+        );
+
+        let err = Parser::new(input).parse().unwrap_err();
+        assert_eq!(err, ParseError::ExpectedColon);
+    }
+
+    #[test]
+    fn test_error_breadcrumbs_in_value() {
+        let input = "[[1, 2], {\"a\": [true, nul]}]".to_string();
+        let options = ParserOptions {
+            track_context: true,
+            ..Default::default()
+        };
+        let err = Parser::with_options(input, options).parse().unwrap_err();
+
+        match err {
+            ParseError::Context { breadcrumbs, .. } => assert_eq!(
+                breadcrumbs,
+                vec![
+                    Breadcrumb::Index(1),
+                    Breadcrumb::Key("a".to_string()),
+                    Breadcrumb::Index(1),
+                ]
+            ),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_reject_noncanonical_numbers() {
+        let options = ParserOptions {
+            reject_noncanonical_numbers: true,
+            ..Default::default()
+        };
+
+        for input in ["[1.50]", "[1e01]"] {
+            let mut parser = Parser::with_options(input.to_string(), options.clone());
+            assert!(matches!(
+                parser.parse(),
+                Err(ParseError::NonCanonicalNumber(_))
+            ));
+
+            let mut parser = Parser::new(input.to_string());
+            assert!(parser.parse().is_ok());
+        }
+
+        let mut parser = Parser::with_options("[1.5, 1e1]".to_string(), options);
+        assert!(parser.parse().is_ok());
+    }
+
+    #[test]
+    fn test_non_finite() {
+        let input = "{\"a\": NaN, \"b\": [Infinity, -Infinity]}".to_string();
+        let options = ParserOptions {
+            allow_non_finite: true,
+            ..Default::default()
+        };
+        let value = Parser::with_options(input.clone(), options)
+            .parse()
+            .unwrap();
+
+        assert_eq!(value.to_string(), "{\"a\":NaN,\"b\":[Infinity,-Infinity]}");
+        assert!(Parser::new(input).parse().is_err());
+    }
+
+    #[test]
+    fn test_numeric_keys() {
+        let input = "{1: \"a\", 2.5: \"b\"}".to_string();
+        let options = ParserOptions {
+            allow_numeric_keys: true,
+            ..Default::default()
+        };
+        let mut m = IndexMap::<String, JValue>::new();
+        m.insert("1".to_string(), JValue::String("a".to_string()));
+        m.insert("2.5".to_string(), JValue::String("b".to_string()));
+
+        assert_eq!(
+            Parser::with_options(input.clone(), options).parse(),
+            Ok(JValue::Object(m))
+        );
+        assert!(matches!(
+            Parser::new(input).parse(),
+            Err(ParseError::UnexpectedToken(JToken::Number(_)))
+        ));
+    }
+
+    #[test]
+    fn test_raw_number_error_offsets() {
+        for raw_numbers in [false, true] {
+            let parse = |input: &str| {
+                let options = ParserOptions {
+                    raw_numbers,
+                    allow_numeric_keys: true,
+                    ..Default::default()
+                };
+                Parser::with_options(input.to_string(), options).parse()
+            };
+
+            assert_eq!(
+                parse("[1, 2, 3e]"),
+                Err(ParseError::Tokenize(TokenizeError::InvalidNumber(7)))
+            );
+            assert_eq!(
+                parse("{\"a\": 1, 2e+: 3}"),
+                Err(ParseError::Tokenize(TokenizeError::InvalidNumber(9)))
+            );
+        }
+    }
+
+    #[test]
+    fn test_numeric_keys_are_normalized() {
+        for raw_numbers in [false, true] {
+            let options = ParserOptions {
+                allow_numeric_keys: true,
+                raw_numbers,
+                ..Default::default()
+            };
+            let value = Parser::with_options("{2.50: \"x\", 1e2: \"y\"}".to_string(), options)
+                .parse()
+                .unwrap();
+
+            assert_eq!(value.to_string(), "{\"2.5\":\"x\",\"100\":\"y\"}");
+        }
+    }
+
+    #[test]
+    fn test_raw_numbers() {
+        let input = "{\"a\": [1, 2.50, -3e2]}";
+        let options = ParserOptions {
+            raw_numbers: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Parser::with_options(input.to_string(), options).parse(),
+            Parser::new(input.to_string()).parse()
+        );
+    }
+
+    #[test]
+    fn test_set_options_between_documents() {
+        let input = "[\" a \", 1]\n[\" b \", NaN]".to_string();
+        let mut values = Parser::new(input).into_values();
+
+        let first = values.next().unwrap().unwrap();
+        assert_eq!(first.to_string(), "[\" a \",1]");
+
+        values.set_options(ParserOptions {
+            trim_strings: true,
+            allow_non_finite: true,
+            ..Default::default()
+        });
+        let second = values.next().unwrap().unwrap();
+        assert_eq!(second.to_string(), "[\"b\",NaN]");
+        assert!(values.next().is_none());
+    }
+
+    #[test]
+    fn test_set_options_strict_rejects() {
+        let input = "[NaN]".to_string();
+        let mut parser = Parser::with_options(
+            input,
+            ParserOptions {
+                allow_non_finite: true,
+                ..Default::default()
+            },
+        );
+        parser.set_options(ParserOptions::default());
+
+        assert!(parser.parse().is_err());
+    }
+
+    #[test]
+    fn test_peek_kind() {
+        let mut parser = Parser::new("[1, \"a\"] {\"k\": null}".to_string());
+
+        assert_eq!(parser.peek_kind(), Some(TokenKind::LeftBracket));
+        assert_eq!(parser.peek_kind(), Some(TokenKind::LeftBracket));
+        parser.parse_array().unwrap();
+        assert_eq!(parser.peek_kind(), Some(TokenKind::LeftBrace));
+        parser.parse_object().unwrap();
+        assert_eq!(parser.peek_kind(), None);
+    }
+
+    #[test]
+    fn test_object_capacity_hint() {
+        let input = "{\"a\": {\"b\": 1}, \"c\": 2}".to_string();
+        let options = ParserOptions {
+            object_capacity_hint: Some(usize::MAX),
+            ..Default::default()
+        };
+        let hinted = Parser::with_options(input.clone(), options).parse();
+
+        assert_eq!(hinted, Parser::new(input).parse());
+    }
+
+    #[test]
+    fn test_record_duplicate_keys() {
+        let input =
+            "{\"a\": [1, {\"b\": 1, \"b\": 2}], \"c\": {\"x/y\": 1, \"x/y\": 2, \"x/y\": 3}}";
+        let mut parser = Parser::with_options(
+            input.to_string(),
+            ParserOptions {
+                record_duplicate_keys: true,
+                ..Default::default()
+            },
+        );
+        let value = parser.parse().unwrap();
+
+        assert_eq!(parser.duplicate_keys(), ["/a/1/b", "/c/x~1y", "/c/x~1y"]);
+        assert_eq!(value.to_string(), "{\"a\":[1,{\"b\":2}],\"c\":{\"x/y\":3}}");
+    }
+
+    #[test]
+    fn test_normalize_numbers() {
+        let options = ParserOptions {
+            normalize_numbers: true,
+            ..Default::default()
+        };
+        let input = "[1E2, 100, 100.0, +1e2, 1.50, -0.0, 0.000125e-2, 12.5e-1, 1e21, -25e-9]";
+        let value = Parser::with_options(
+            input.to_string(),
+            ParserOptions {
+                allow_leading_plus: true,
+                ..options.clone()
+            },
+        )
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            value.to_string(),
+            "[100,100,100,100,1.5,0,0.00000125,1.25,1e21,-2.5e-8]"
+        );
+        assert_eq!(value.get_index(0), value.get_index(1));
+
+        let raw = Parser::with_options(
+            input.replace('+', ""),
+            ParserOptions {
+                raw_numbers: true,
+                ..options
+            },
+        )
+        .parse()
+        .unwrap();
+        assert_eq!(raw.to_string(), value.to_string());
+    }
+
+    fn parse_with_strategy(
+        input: &str,
+        strategy: DuplicateKeyStrategy,
+    ) -> Result<JValue, ParseError> {
+        let options = ParserOptions {
+            duplicate_key_strategy: strategy,
+            ..Default::default()
+        };
+        Parser::with_options(input.to_string(), options).parse()
+    }
+
+    #[test]
+    fn test_duplicate_key_strategy() {
+        let input = "{\"a\":1,\"a\":2}";
+        let parse = |strategy| parse_with_strategy(input, strategy).map(|v| v.to_string());
+
+        assert_eq!(
+            parse(DuplicateKeyStrategy::LastWins),
+            Ok("{\"a\":2}".to_string())
+        );
+        assert_eq!(
+            parse(DuplicateKeyStrategy::FirstWins),
+            Ok("{\"a\":1}".to_string())
+        );
+        assert_eq!(
+            parse(DuplicateKeyStrategy::Reject),
+            Err(ParseError::DuplicateKey("a".to_string()))
+        );
+        assert_eq!(
+            parse(DuplicateKeyStrategy::Collect),
+            Ok("{\"a\":[1,2]}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_duplicate_key_collect() {
+        // an array value is collected like any other, not extended
+        let input = "{\"a\":[1],\"b\":{\"c\":0,\"c\":1},\"a\":2,\"a\":[3]}";
+        let value = parse_with_strategy(input, DuplicateKeyStrategy::Collect).unwrap();
+
+        assert_eq!(value.to_string(), "{\"a\":[[1],2,[3]],\"b\":{\"c\":[0,1]}}");
+
+        let options = ParserOptions {
+            duplicate_key_strategy: DuplicateKeyStrategy::FirstWins,
+            ..Default::default()
+        };
+        let borrowed = Parser::borrowed(input, options).parse_borrowed().unwrap();
+        assert_eq!(
+            JValue::from(borrowed).to_string(),
+            "{\"a\":[1],\"b\":{\"c\":0}}"
+        );
+    }
+
+    // xorshift, so the inputs are the same on every run
+    fn random_inputs(count: usize) -> Vec<String> {
+        const JSONISH: &[u8] = b"{}[]:,'\"\\/ \t\n-+.eE0123456789nultrfasNIy\x01\xc3\xa9";
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        (0..count)
+            .map(|_| {
+                let len = (next() % 48) as usize;
+                let bytes = (0..len)
+                    .map(|_| match next() % 4 {
+                        // mostly characters that steer the parser somewhere
+                        0 => next() as u8,
+                        _ => JSONISH[(next() % JSONISH.len() as u64) as usize],
+                    })
+                    .collect::<Vec<_>>();
+                String::from_utf8_lossy(&bytes).into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_random_input_never_panics() {
+        let option_sets = [
+            ParserOptions::default(),
+            ParserOptions {
+                trim_strings: true,
+                track_context: true,
+                error_snippets: true,
+                reject_noncanonical_numbers: true,
+                normalize_numbers: true,
+                allow_non_finite: true,
+                max_total_string_bytes: Some(8),
+                max_input_len: Some(40),
+                max_string_len: Some(4),
+                allow_single_quotes: true,
+                allow_unquoted_keys: true,
+                allow_hex_numbers: true,
+                allow_numeric_keys: true,
+                allow_leading_plus: true,
+                allow_comments: true,
+                record_comments: true,
+                key_validator: Some(|k| !k.is_empty()),
+                raw_numbers: true,
+                record_duplicate_keys: true,
+                duplicate_key_strategy: DuplicateKeyStrategy::Collect,
+                object_capacity_hint: Some(1 << 40),
+                max_depth: Some(2),
+                max_interned_keys: Some(1),
+                reject_uninterned_keys: true,
+            },
+        ];
+
+        for input in random_inputs(4000) {
+            for options in &option_sets {
+                let result = std::panic::catch_unwind(|| {
+                    let _ = Parser::with_options(input.clone(), options.clone()).parse();
+                    let _ = Parser::with_options(input.clone(), options.clone()).parse_stream();
+                    let _ = Parser::borrowed(&input, options.clone()).parse_interned();
+                });
+                assert!(result.is_ok(), "panicked on {:?}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stray_closer() {
+        for (input, token) in [("]", JToken::RightBracket), ("}", JToken::RightBrace)] {
+            let error = Parser::new(input.to_string()).parse().unwrap_err();
+
+            assert_eq!(error, ParseError::UnexpectedToken(token));
+            assert_eq!(error.to_string(), format!("unexpected `{}`", input));
+        }
+        assert_eq!(
+            Parser::new("[1] }".to_string()).parse(),
+            Err(ParseError::TrailingTokens(JToken::RightBrace))
+        );
+    }
+
+    #[test]
+    fn test_mismatched_closer() {
+        let array = ParseError::MismatchedCloser {
+            expected: ']',
+            found: '}',
+        };
+        let object = ParseError::MismatchedCloser {
+            expected: '}',
+            found: ']',
+        };
+        for (input, error) in [
+            ("[}", &array),
+            ("[1}", &array),
+            ("[1,}", &array),
+            ("{\"a\": [{}}", &array),
+            ("{]", &object),
+            ("{\"a\": 1]", &object),
+            ("{\"a\": 1,]", &object),
+            ("[{\"a\": []]", &object),
+        ] {
+            assert_eq!(
+                Parser::new(input.to_string()).parse().as_ref(),
+                Err(error),
+                "{}",
+                input
+            );
+        }
+        assert_eq!(
+            array.to_string(),
+            "expected `]` to close the array, found `}`"
+        );
+        assert_eq!(
+            object.to_string(),
+            "expected `}` to close the object, found `]`"
+        );
+    }
+
+    #[test]
+    fn test_wrong_opening_token() {
+        let mut parser = Parser::new("[1]".to_string());
+
+        assert_eq!(
+            parser.parse_object(),
+            Err(ParseError::UnexpectedToken(JToken::LeftBracket))
+        );
+    }
+
+    #[test]
+    fn test_error_snippet() {
+        let options = ParserOptions {
+            error_snippets: true,
+            ..Default::default()
+        };
+        let input = "{\"name\": \"a very long value\", \"age\": , \"x\": 1}";
+        let error = Parser::with_options(input.to_string(), options.clone())
+            .parse()
+            .unwrap_err();
+
+        assert_eq!(error.root(), &ParseError::ExpectedValue);
+        assert_eq!(
+            error.to_string(),
+            "expected a value at byte 37\n   \
+             value\", \"age\": , \"x\": 1}\n                  \
+             ^"
+        );
+
+        let error = Parser::with_options("[1,\n\"é\tx]".to_string(), options)
+            .parse()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unescaped control character '\\t' in string at 7 at byte 7\n  \
+             [1, \"é x]\n        \
+             ^"
+        );
+    }
+
+    #[test]
+    fn test_error_snippet_eof() {
+        let options = ParserOptions {
+            error_snippets: true,
+            ..Default::default()
+        };
+        let error = Parser::with_options("[1, 2".to_string(), options)
+            .parse()
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "unexpected end of input at byte 5\n  [1, 2\n       ^"
+        );
+    }
+
+    #[test]
+    fn test_unquoted_keys() {
+        let options = ParserOptions {
+            allow_unquoted_keys: true,
+            ..Default::default()
+        };
+        let value = Parser::with_options(
+            "{foo: 1, bar_2: {null: [true]}}".to_string(),
+            options.clone(),
+        )
+        .parse()
+        .unwrap();
+
+        assert_eq!(value.to_string(), "{\"foo\":1,\"bar_2\":{\"null\":[true]}}");
+        // only keys may be bare
+        assert_eq!(
+            Parser::with_options("{foo: bar}".to_string(), options).parse(),
+            Err(ParseError::ExpectedValue)
+        );
+    }
+
+    #[test]
+    fn test_unquoted_keys_strict() {
+        assert_eq!(
+            Parser::new("{foo: 1}".to_string()).parse(),
+            Err(ParseError::Tokenize(TokenizeError::InvalidKeyword(
+                "foo".to_string(),
+                1
+            )))
+        );
+        assert_eq!(
+            Parser::new("{null: 1}".to_string()).parse(),
+            Err(ParseError::UnexpectedToken(JToken::Null))
+        );
+    }
+
+    #[test]
+    fn test_parse_one() {
+        let mut parser = Parser::new("{\"a\": 1}\n[true]  ".to_string());
+
+        assert_eq!(
+            parser.parse_one().unwrap().unwrap().to_string(),
+            "{\"a\":1}"
+        );
+        assert_eq!(parser.parse_one().unwrap().unwrap().to_string(), "[true]");
+        assert_eq!(parser.parse_one(), Ok(None));
+        assert_eq!(parser.parse_one(), Ok(None));
+    }
+
+    #[test]
+    fn test_record_comments() {
+        let input = "// the config
+{
+  // where to listen
+  \"port\": /* http */ 8080, // default
+  \"hosts\": [
+    \"a\", /* primary */
+    \"b\"
+    // no more
+  ],
+  \"empty\": { /* nothing */ }
+} // end";
+        let options = ParserOptions {
+            record_comments: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(input.to_string(), options.clone());
+        let value = parser.parse().unwrap();
+
+        let comments = |leading: &[&str], trailing: &[&str]| Comments {
+            leading: leading.iter().map(|c| c.to_string()).collect(),
+            trailing: trailing.iter().map(|c| c.to_string()).collect(),
+        };
+        let expected = [
+            ("", comments(&["// the config"], &["// end"])),
+            (
+                "/port",
+                comments(&["// where to listen", "/* http */"], &["// default"]),
+            ),
+            ("/hosts/0", comments(&[], &["/* primary */"])),
+            ("/hosts/1", comments(&[], &["// no more"])),
+            ("/empty", comments(&[], &["/* nothing */"])),
+        ];
+        assert_eq!(parser.comments().len(), expected.len());
+        for (path, expected) in expected {
+            assert_eq!(parser.comments().get(path), Some(&expected), "{}", path);
+        }
+        assert_eq!(
+            value.to_string(),
+            "{\"port\":8080,\"hosts\":[\"a\",\"b\"],\"empty\":{}}"
+        );
+
+        // the table is per document
+        let mut parser = Parser::with_options("// one\n[1]\n[2]".to_string(), options);
+        parser.parse_one().unwrap();
+        parser.parse_one().unwrap();
+        assert!(parser.comments().is_empty());
+    }
+
+    #[test]
+    fn test_key_validator() {
+        let options = ParserOptions {
+            key_validator: Some(|k| !k.chars().any(char::is_control)),
+            error_snippets: true,
+            ..Default::default()
+        };
+        // rejected even though the newline is validly escaped
+        let input = "{\"ok\": {\"a\\nb\": 1}}";
+        let error = Parser::with_options(input.to_string(), options.clone())
+            .parse()
+            .unwrap_err();
+
+        assert_eq!(error.root(), &ParseError::InvalidKey("a\nb".to_string()));
+        assert!(
+            matches!(error, ParseError::At { pos: 8, .. }),
+            "{:?}",
+            error
+        );
+
+        let value = Parser::with_options("{\"ok\": [{\"b\": \"x\\ny\"}]}".to_string(), options)
+            .parse()
+            .unwrap();
+        assert_eq!(value.to_string(), "{\"ok\":[{\"b\":\"x\\ny\"}]}");
+    }
+
+    #[test]
+    fn test_empty_input() {
+        for input in ["", "   ", " \t\r\n "] {
+            assert_eq!(
+                Parser::new(input.to_string()).parse(),
+                Err(ParseError::EmptyInput),
+                "{:?}",
+                input
+            );
+        }
+        assert_eq!(
+            ParseError::EmptyInput.to_string(),
+            "expected a JSON object or array, but the input is empty"
+        );
+
+        let mut events = 0;
+        let result = Parser::new(" ".to_string()).parse_events(|_| events += 1);
+        assert_eq!(result, Err(ParseError::EmptyInput));
+        assert_eq!(events, 0);
+        // a stream of no documents is fine
+        assert_eq!(Parser::new(" ".to_string()).parse_stream(), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_token_error_display() {
+        let error = Parser::new("{\"a\": 1}}".to_string()).parse().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unexpected `}` after the end of the document"
+        );
+
+        let error = Parser::new("[1 \"x\"]".to_string()).parse().unwrap_err();
+        assert_eq!(error.to_string(), "unexpected `\"x\"`");
+    }
+
+    #[test]
+    fn test_parse_all() {
+        let values = Parser::parse_all("{} [] true 3").unwrap();
+        assert_eq!(
+            values,
+            [
+                JValue::Object(IndexMap::new()),
+                JValue::Array(vec![]),
+                JValue::Bool(true),
+                JValue::Number(Number::new(3, None, None)),
+            ]
+        );
+
+        let values = Parser::parse_all("\"a\"{\"b\":[null]}\t-1.5\n").unwrap();
+        let texts = values.iter().map(JValue::to_string).collect::<Vec<_>>();
+        assert_eq!(texts, ["\"a\"", "{\"b\":[null]}", "-1.5"]);
+
+        assert_eq!(Parser::parse_all("  "), Ok(vec![]));
+        assert_eq!(Parser::parse_all("1 [2"), Err(ParseError::UnexpectedEof));
+        assert_eq!(
+            Parser::parse_all("1 }"),
+            Err(ParseError::UnexpectedToken(JToken::RightBrace))
+        );
+        assert_eq!(
+            Parser::parse_all("1 2 3]"),
+            Err(ParseError::UnexpectedToken(JToken::RightBracket))
+        );
+    }
+
+    #[test]
+    fn test_parse_one_then_error() {
+        let mut parser = Parser::new("[1] [2,".to_string());
+
+        assert!(parser.parse_one().unwrap().is_some());
+        assert_eq!(parser.parse_one(), Err(ParseError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_from_reader() {
+        let input: &[u8] = b"{\"a\": [1, \"\xc3\xa9\"]}";
+        let value = Parser::from_reader(input, ParserOptions::default())
+            .unwrap()
+            .parse()
+            .unwrap();
+
+        assert_eq!(value.to_string(), "{\"a\":[1,\"é\"]}");
+    }
+
+    #[test]
+    fn test_from_reader_invalid_utf8() {
+        // "é" cut off after its first byte
+        let truncated: &[u8] = b"[\"abc\xc3";
+        let stray: &[u8] = b"[\"ab\xff\"]";
+
+        for (input, pos) in [(truncated, 5), (stray, 4)] {
+            let result = Parser::from_reader(input, ParserOptions::default());
+
+            assert!(matches!(
+                result,
+                Err(ReadError::Parse(ParseError::Tokenize(TokenizeError::InvalidUtf8(p)))) if p == pos
+            ));
+        }
+    }
+
+    #[test]
+    fn test_from_reader_max_input_len() {
+        let options = ParserOptions {
+            max_input_len: Some(4),
+            ..Default::default()
+        };
+        let result = Parser::from_reader(&b"[1, 2, 3]"[..], options);
+
+        assert!(matches!(
+            result,
+            Err(ReadError::Parse(ParseError::Tokenize(
+                TokenizeError::InputTooLong(4)
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        const DEPTH: usize = 100_000;
+        let arrays = format!("{}{}", "[".repeat(DEPTH), "]".repeat(DEPTH));
+        let objects = format!("{}null{}", "{\"a\": ".repeat(DEPTH), "}".repeat(DEPTH));
+
+        // dropping recurses, so the values are taken apart by hand
+        let dismantle = |value: JValue| {
+            let mut stack = vec![value];
+            while let Some(mut value) = stack.pop() {
+                match &mut value {
+                    JValue::Array(arr) => stack.append(arr),
+                    JValue::Object(m) => stack.extend(std::mem::take(m).into_values()),
+                    _ => {}
+                }
+            }
+        };
+
+        let options = ParserOptions {
+            max_depth: None,
+            ..Default::default()
+        };
+        let value = Parser::with_options(arrays, options.clone())
+            .parse()
+            .unwrap();
+        assert_eq!(value.depth(), DEPTH);
+        dismantle(value);
+        let value = Parser::with_options(objects.clone(), options.clone())
+            .parse()
+            .unwrap();
+        assert_eq!(value.depth(), DEPTH);
+        dismantle(value);
+
+        let mut events = 0;
+        Parser::with_options(objects, options)
+            .parse_events(|_| events += 1)
+            .unwrap();
+        assert_eq!(events, 3 * DEPTH + 1);
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let parse = |input: &str, max_depth| {
+            let options = ParserOptions {
+                max_depth,
+                ..Default::default()
+            };
+            Parser::with_options(input.to_string(), options).parse()
+        };
+
+        assert!(parse("[[1], {\"a\": {}}]", Some(3)).is_ok());
+        assert_eq!(
+            parse("[[1], {\"a\": {}}]", Some(2)),
+            Err(ParseError::TooDeep(2))
+        );
+        assert_eq!(parse("[[[]]]", Some(2)), Err(ParseError::TooDeep(2)));
+        assert_eq!(parse("[[[]]]", None).map(|v| v.depth()), Ok(3));
+
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Parser::new(nested(128)).parse().is_ok());
+        assert_eq!(
+            Parser::new(nested(100_000)).parse(),
+            Err(ParseError::TooDeep(128))
+        );
+        assert_eq!(
+            ParseError::TooDeep(2).to_string(),
+            "nesting exceeds the limit of 2 levels"
+        );
+    }
+
+    #[test]
+    fn test_deep_nesting_error() {
+        let input = format!("{}1, }}", "[".repeat(100_000));
+        let options = ParserOptions {
+            track_context: true,
+            max_depth: None,
+            ..Default::default()
+        };
+        let err = Parser::with_options(input, options).parse().unwrap_err();
+
+        let ParseError::Context { error, breadcrumbs } = err else {
+            panic!("expected context");
+        };
+        assert_eq!(
+            *error,
+            ParseError::MismatchedCloser {
+                expected: ']',
+                found: '}'
+            }
+        );
+        assert_eq!(breadcrumbs.len(), 100_000);
+    }
+
+    #[test]
+    fn test_events() {
+        let input = "{\"a\": [1, {\"b\": null}], \"c\": \"d\"}".to_string();
+        let mut events = Vec::new();
+        Parser::new(input).parse_events(|e| events.push(e)).unwrap();
+
+        assert_eq!(
+            events,
+            [
+                Event::StartObject,
+                Event::Key("a".to_string()),
+                Event::StartArray,
+                Event::Value(JValue::Number("1".parse().unwrap())),
+                Event::StartObject,
+                Event::Key("b".to_string()),
+                Event::Value(JValue::Null),
+                Event::EndObject,
+                Event::EndArray,
+                Event::Key("c".to_string()),
+                Event::Value(JValue::String("d".to_string())),
+                Event::EndObject,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_events_count_elements() {
+        let records = (0..1000)
+            .map(|i| format!("{{\"id\": {}, \"tags\": [\"x\", \"y\"]}}", i))
+            .collect::<Vec<_>>();
+        let input = format!("[{}]", records.join(","));
+
+        // elements of the top-level array are whatever starts at depth 1
+        let mut depth = 0;
+        let mut count = 0;
+        Parser::new(input)
+            .parse_events(|e| match e {
+                Event::StartObject | Event::StartArray => {
+                    if depth == 1 {
+                        count += 1;
+                    }
+                    depth += 1;
+                }
+                Event::EndObject | Event::EndArray => depth -= 1,
+                Event::Value(_) if depth == 1 => count += 1,
+                _ => {}
+            })
+            .unwrap();
+
+        assert_eq!(count, 1000);
+    }
+
+    #[test]
+    fn test_events_error() {
+        let mut events = 0;
+        let result = Parser::new("[1, 2, }".to_string()).parse_events(|_| events += 1);
+
+        assert_eq!(
+            result,
+            Err(ParseError::MismatchedCloser {
+                expected: ']',
+                found: '}'
+            })
+        );
+        assert_eq!(events, 3);
     }
 }
@@ -0,0 +1,544 @@
+use std::fmt::Display;
+
+use indexmap::IndexMap;
+
+use crate::builder::ObjectBuilder;
+use crate::parser::JValue;
+use crate::value::{escape_pointer_token, parse_index, parse_pointer};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    /// The patch document or one of its operations is not well formed.
+    Malformed(String),
+    InvalidPointer(String),
+    PathNotFound(String),
+    TestFailed(String),
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatchError::Malformed(msg) => write!(f, "malformed patch: {}", msg),
+            PatchError::InvalidPointer(p) => write!(f, "invalid JSON pointer {:?}", p),
+            PatchError::PathNotFound(p) => write!(f, "path {:?} does not exist", p),
+            PatchError::TestFailed(p) => write!(f, "test failed at {:?}", p),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Why `JValue::merge_arrays_elementwise` could not pair up two values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeError {
+    NotArrays,
+    /// The lengths of the two arrays, in argument order.
+    LengthMismatch(usize, usize),
+}
+
+impl Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::NotArrays => write!(f, "both values must be arrays"),
+            MergeError::LengthMismatch(a, b) => {
+                write!(f, "arrays of length {} and {} cannot be paired up", a, b)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl JValue {
+    /// Applies `patch` as an RFC 7386 JSON Merge Patch. Object members are
+    /// merged recursively, a `null` member removes the key, and any other
+    /// patch replaces the target outright.
+    pub fn merge_patch(&mut self, patch: &JValue) {
+        let JValue::Object(pm) = patch else {
+            *self = patch.clone();
+            return;
+        };
+
+        if !self.is_object() {
+            *self = JValue::Object(IndexMap::new());
+        }
+        let JValue::Object(m) = self else {
+            unreachable!()
+        };
+
+        for (k, v) in pm {
+            if v.is_null() {
+                m.shift_remove(k);
+            } else {
+                m.entry(k.clone()).or_insert(JValue::Null).merge_patch(v);
+            }
+        }
+    }
+
+    /// Deep-merges each element of `other` into the element of this array at
+    /// the same index. Objects are merged key by key, recursively, keeping
+    /// keys only `self` has; anything else, arrays included, is replaced by
+    /// `other`'s value. Unlike `merge_patch`, `null` is merged as a value.
+    /// On error `self` is left unchanged.
+    pub fn merge_arrays_elementwise(&mut self, other: &JValue) -> Result<(), MergeError> {
+        let (JValue::Array(arr), JValue::Array(more)) = (self, other) else {
+            return Err(MergeError::NotArrays);
+        };
+        if arr.len() != more.len() {
+            return Err(MergeError::LengthMismatch(arr.len(), more.len()));
+        }
+        for (v, w) in arr.iter_mut().zip(more) {
+            deep_merge(v, w);
+        }
+        Ok(())
+    }
+
+    /// Applies an RFC 6902 JSON Patch: an array of `add`, `remove`, `replace`,
+    /// `move`, `copy` and `test` operations. The patch is applied as a whole;
+    /// if any operation fails, `self` is left unchanged.
+    pub fn apply_patch(&mut self, ops: &JValue) -> Result<(), PatchError> {
+        let JValue::Array(ops) = ops else {
+            return Err(PatchError::Malformed("patch must be an array".to_string()));
+        };
+
+        let mut doc = self.clone();
+        for op in ops {
+            apply_operation(&mut doc, op)?;
+        }
+        *self = doc;
+        Ok(())
+    }
+
+    /// Builds an RFC 6902 JSON Patch of `add`, `remove` and `replace`
+    /// operations that turns `old` into `new` under `apply_patch`. Objects and
+    /// arrays are compared member by member; array elements by position, so an
+    /// insertion near the front shows up as a run of replacements.
+    pub fn diff(old: &JValue, new: &JValue) -> JValue {
+        let mut ops = Vec::new();
+        diff_into(&mut ops, &mut String::new(), old, new);
+        JValue::Array(ops)
+    }
+}
+
+fn deep_merge(target: &mut JValue, other: &JValue) {
+    match (target, other) {
+        (JValue::Object(m), JValue::Object(om)) => {
+            for (k, w) in om {
+                match m.get_mut(k) {
+                    Some(v) => deep_merge(v, w),
+                    None => {
+                        m.insert(k.clone(), w.clone());
+                    }
+                }
+            }
+        }
+        (target, other) => *target = other.clone(),
+    }
+}
+
+fn diff_into(ops: &mut Vec<JValue>, path: &mut String, old: &JValue, new: &JValue) {
+    if old == new {
+        return;
+    }
+    let len = path.len();
+    match (old, new) {
+        (JValue::Object(a), JValue::Object(b)) => {
+            for (k, v) in a {
+                path.push_str(&format!("/{}", escape_pointer_token(k)));
+                match b.get(k) {
+                    Some(w) => diff_into(ops, path, v, w),
+                    None => ops.push(operation("remove", path, None)),
+                }
+                path.truncate(len);
+            }
+            for (k, w) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+                path.push_str(&format!("/{}", escape_pointer_token(k)));
+                ops.push(operation("add", path, Some(w)));
+                path.truncate(len);
+            }
+        }
+        (JValue::Array(a), JValue::Array(b)) => {
+            for (i, (v, w)) in a.iter().zip(b).enumerate() {
+                path.push_str(&format!("/{}", i));
+                diff_into(ops, path, v, w);
+                path.truncate(len);
+            }
+            // from the back, so the indices still to remove stay put
+            for i in (b.len()..a.len()).rev() {
+                path.push_str(&format!("/{}", i));
+                ops.push(operation("remove", path, None));
+                path.truncate(len);
+            }
+            for (i, w) in b.iter().enumerate().skip(a.len()) {
+                path.push_str(&format!("/{}", i));
+                ops.push(operation("add", path, Some(w)));
+                path.truncate(len);
+            }
+        }
+        _ => ops.push(operation("replace", path, Some(new))),
+    }
+}
+
+fn operation(op: &str, path: &str, value: Option<&JValue>) -> JValue {
+    let op = ObjectBuilder::new()
+        .insert("op", JValue::String(op.to_string()))
+        .insert("path", JValue::String(path.to_string()));
+    match value {
+        Some(v) => op.insert("value", v.clone()).build(),
+        None => op.build(),
+    }
+}
+
+fn apply_operation(doc: &mut JValue, op: &JValue) -> Result<(), PatchError> {
+    let JValue::Object(m) = op else {
+        return Err(PatchError::Malformed(
+            "operation must be an object".to_string(),
+        ));
+    };
+    let member = |name: &str| {
+        m.get(name)
+            .ok_or_else(|| PatchError::Malformed(format!("missing {:?}", name)))
+    };
+    let string_member = |name: &str| match member(name)? {
+        JValue::String(s) => Ok(s.as_str()),
+        _ => Err(PatchError::Malformed(format!(
+            "{:?} must be a string",
+            name
+        ))),
+    };
+
+    let path = string_member("path")?;
+    let tokens = pointer_tokens(path)?;
+    match string_member("op")? {
+        "add" => add(doc, &tokens, member("value")?.clone(), path),
+        "remove" => remove(doc, &tokens, path).map(|_| ()),
+        "replace" => {
+            let target = lookup_mut(doc, &tokens)
+                .ok_or_else(|| PatchError::PathNotFound(path.to_string()))?;
+            *target = member("value")?.clone();
+            Ok(())
+        }
+        "move" => {
+            let from = string_member("from")?;
+            let from_tokens = pointer_tokens(from)?;
+            if tokens.len() > from_tokens.len() && tokens.starts_with(&from_tokens) {
+                return Err(PatchError::Malformed(format!(
+                    "cannot move {:?} into its own child {:?}",
+                    from, path
+                )));
+            }
+            let value = remove(doc, &from_tokens, from)?;
+            add(doc, &tokens, value, path)
+        }
+        "copy" => {
+            let from = string_member("from")?;
+            let value = lookup(doc, &pointer_tokens(from)?)
+                .ok_or_else(|| PatchError::PathNotFound(from.to_string()))?
+                .clone();
+            add(doc, &tokens, value, path)
+        }
+        // RFC 6902 compares numbers by value, so `1` passes a test for `1.0`
+        "test" => match lookup(doc, &tokens) {
+            Some(v) if v.semantic_eq(member("value")?) => Ok(()),
+            _ => Err(PatchError::TestFailed(path.to_string())),
+        },
+        op => Err(PatchError::Malformed(format!("unknown op {:?}", op))),
+    }
+}
+
+fn pointer_tokens(path: &str) -> Result<Vec<String>, PatchError> {
+    parse_pointer(path).ok_or_else(|| PatchError::InvalidPointer(path.to_string()))
+}
+
+fn lookup<'a>(doc: &'a JValue, tokens: &[String]) -> Option<&'a JValue> {
+    tokens.iter().try_fold(doc, |v, t| v.child(t))
+}
+
+fn lookup_mut<'a>(doc: &'a mut JValue, tokens: &[String]) -> Option<&'a mut JValue> {
+    tokens.iter().try_fold(doc, |v, t| v.child_mut(t))
+}
+
+fn add(doc: &mut JValue, tokens: &[String], value: JValue, path: &str) -> Result<(), PatchError> {
+    let Some((last, parent)) = tokens.split_last() else {
+        *doc = value;
+        return Ok(());
+    };
+    let not_found = || PatchError::PathNotFound(path.to_string());
+
+    match lookup_mut(doc, parent).ok_or_else(not_found)? {
+        JValue::Object(m) => {
+            m.insert(last.clone(), value);
+        }
+        JValue::Array(arr) if last == "-" => arr.push(value),
+        JValue::Array(arr) => match parse_index(last) {
+            Some(i) if i <= arr.len() => arr.insert(i, value),
+            _ => return Err(not_found()),
+        },
+        _ => return Err(not_found()),
+    }
+    Ok(())
+}
+
+fn remove(doc: &mut JValue, tokens: &[String], path: &str) -> Result<JValue, PatchError> {
+    let not_found = || PatchError::PathNotFound(path.to_string());
+    let Some((last, parent)) = tokens.split_last() else {
+        return Err(PatchError::Malformed(
+            "cannot remove the whole document".to_string(),
+        ));
+    };
+
+    match lookup_mut(doc, parent).ok_or_else(not_found)? {
+        JValue::Object(m) => m.shift_remove(last).ok_or_else(not_found),
+        JValue::Array(arr) => match parse_index(last) {
+            Some(i) if i < arr.len() => Ok(arr.remove(i)),
+            _ => Err(not_found()),
+        },
+        _ => Err(not_found()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(s: &str) -> JValue {
+        Parser::new(s.to_string()).parse().unwrap()
+    }
+
+    #[test]
+    fn test_merge_patch_add() {
+        let mut target = parse("{\"a\": 1}");
+        target.merge_patch(&parse("{\"b\": 2}"));
+
+        assert_eq!(target.to_string(), "{\"a\":1,\"b\":2}");
+    }
+
+    #[test]
+    fn test_merge_patch_delete() {
+        let mut target = parse("{\"a\": 1, \"b\": 2, \"c\": 3}");
+        target.merge_patch(&parse("{\"b\": null, \"missing\": null}"));
+
+        assert_eq!(target.to_string(), "{\"a\":1,\"c\":3}");
+    }
+
+    #[test]
+    fn test_merge_patch_nested() {
+        let mut target = parse("{\"a\": {\"b\": 1, \"c\": {\"d\": 2}}, \"e\": 3}");
+        target.merge_patch(&parse(
+            "{\"a\": {\"c\": {\"d\": null, \"f\": 4}}, \"g\": {\"h\": null, \"i\": 5}}",
+        ));
+
+        assert_eq!(
+            target.to_string(),
+            "{\"a\":{\"b\":1,\"c\":{\"f\":4}},\"e\":3,\"g\":{\"i\":5}}"
+        );
+    }
+
+    #[test]
+    fn test_merge_patch_replace() {
+        let mut target = parse("{\"a\": [1, 2, 3], \"b\": {\"c\": 1}}");
+        target.merge_patch(&parse("{\"a\": [4], \"b\": \"x\"}"));
+        assert_eq!(target.to_string(), "{\"a\":[4],\"b\":\"x\"}");
+
+        target.merge_patch(&parse("[true]"));
+        assert_eq!(target.to_string(), "[true]");
+    }
+
+    #[test]
+    fn test_merge_arrays_elementwise() {
+        let mut target =
+            parse("[{\"id\": 1, \"meta\": {\"a\": 1}}, {\"id\": 2, \"tags\": [\"x\"]}, 3]");
+        target
+            .merge_arrays_elementwise(&parse(
+                "[{\"meta\": {\"b\": 2}, \"ok\": null}, {\"tags\": [\"y\"]}, {\"n\": 3}]",
+            ))
+            .unwrap();
+
+        assert_eq!(
+            target.to_string(),
+            "[{\"id\":1,\"meta\":{\"a\":1,\"b\":2},\"ok\":null},{\"id\":2,\"tags\":[\"y\"]},{\"n\":3}]"
+        );
+    }
+
+    #[test]
+    fn test_merge_arrays_elementwise_errors() {
+        let mut target = parse("[{}, {}]");
+
+        assert_eq!(
+            target.merge_arrays_elementwise(&parse("[{\"a\": 1}]")),
+            Err(MergeError::LengthMismatch(2, 1))
+        );
+        assert_eq!(
+            target.merge_arrays_elementwise(&parse("{}")),
+            Err(MergeError::NotArrays)
+        );
+        assert_eq!(target.to_string(), "[{},{}]");
+    }
+
+    fn patched(doc: &str, patch: &str) -> Result<String, PatchError> {
+        let mut doc = parse(doc);
+        doc.apply_patch(&parse(patch)).map(|_| doc.to_string())
+    }
+
+    #[test]
+    fn test_patch_add() {
+        assert_eq!(
+            patched(
+                "{\"a\": [1, 3]}",
+                "[{\"op\": \"add\", \"path\": \"/b\", \"value\": {}},
+                  {\"op\": \"add\", \"path\": \"/a/1\", \"value\": 2},
+                  {\"op\": \"add\", \"path\": \"/a/-\", \"value\": 4}]"
+            ),
+            Ok("{\"a\":[1,2,3,4],\"b\":{}}".to_string())
+        );
+        assert_eq!(
+            patched(
+                "{}",
+                "[{\"op\": \"add\", \"path\": \"/x/y\", \"value\": 1}]"
+            ),
+            Err(PatchError::PathNotFound("/x/y".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_patch_remove() {
+        assert_eq!(
+            patched(
+                "{\"a\": [1, 2, 3], \"b\": 1}",
+                "[{\"op\": \"remove\", \"path\": \"/a/1\"},
+                  {\"op\": \"remove\", \"path\": \"/b\"}]"
+            ),
+            Ok("{\"a\":[1,3]}".to_string())
+        );
+        assert_eq!(
+            patched("{}", "[{\"op\": \"remove\", \"path\": \"/b\"}]"),
+            Err(PatchError::PathNotFound("/b".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_patch_replace() {
+        assert_eq!(
+            patched(
+                "{\"a\": {\"b~c\": 1}}",
+                "[{\"op\": \"replace\", \"path\": \"/a/b~0c\", \"value\": [true]}]"
+            ),
+            Ok("{\"a\":{\"b~c\":[true]}}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_patch_move() {
+        assert_eq!(
+            patched(
+                "{\"a\": {\"b\": 1}, \"c\": []}",
+                "[{\"op\": \"move\", \"from\": \"/a/b\", \"path\": \"/c/0\"}]"
+            ),
+            Ok("{\"a\":{},\"c\":[1]}".to_string())
+        );
+        assert!(matches!(
+            patched(
+                "{\"a\": {\"b\": 1}}",
+                "[{\"op\": \"move\", \"from\": \"/a\", \"path\": \"/a/b\"}]"
+            ),
+            Err(PatchError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_patch_copy() {
+        assert_eq!(
+            patched(
+                "{\"a\": [1, 2]}",
+                "[{\"op\": \"copy\", \"from\": \"/a\", \"path\": \"/b\"}]"
+            ),
+            Ok("{\"a\":[1,2],\"b\":[1,2]}".to_string())
+        );
+    }
+
+    #[test]
+    fn test_patch_test() {
+        let patch = "[{\"op\": \"test\", \"path\": \"/a\", \"value\": \"x\"},
+                      {\"op\": \"add\", \"path\": \"/b\", \"value\": 1}]";
+        assert_eq!(
+            patched("{\"a\": \"x\"}", patch),
+            Ok("{\"a\":\"x\",\"b\":1}".to_string())
+        );
+
+        let mut doc = parse("{\"a\": \"y\"}");
+        assert_eq!(
+            doc.apply_patch(&parse(patch)),
+            Err(PatchError::TestFailed("/a".to_string()))
+        );
+        assert_eq!(doc.to_string(), "{\"a\":\"y\"}");
+    }
+
+    #[test]
+    fn test_patch_test_numbers() {
+        let test = |value| {
+            format!(
+                "[{{\"op\": \"test\", \"path\": \"/a\", \"value\": {}}}]",
+                value
+            )
+        };
+
+        assert!(patched("{\"a\": 1}", &test("1.0")).is_ok());
+        assert!(patched("{\"a\": 100}", &test("1e2")).is_ok());
+        assert!(patched(
+            "{\"a\": [1e2, {\"b\": 0.50}]}",
+            &test("[100, {\"b\": 0.5}]")
+        )
+        .is_ok());
+        assert_eq!(
+            patched("{\"a\": 1}", &test("1.01")),
+            Err(PatchError::TestFailed("/a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = parse("{\"a\": 1, \"b\": {\"c\": [1, 2, 3], \"d\": \"x\"}, \"gone\": null}");
+        let new = parse("{\"a\": 1, \"b\": {\"c\": [1, 5], \"d\": [\"x\"]}, \"e~/\": true}");
+        let patch = JValue::diff(&old, &new);
+
+        assert_eq!(
+            patch.to_string(),
+            "[{\"op\":\"replace\",\"path\":\"/b/c/1\",\"value\":5},\
+              {\"op\":\"remove\",\"path\":\"/b/c/2\"},\
+              {\"op\":\"replace\",\"path\":\"/b/d\",\"value\":[\"x\"]},\
+              {\"op\":\"remove\",\"path\":\"/gone\"},\
+              {\"op\":\"add\",\"path\":\"/e~0~1\",\"value\":true}]"
+        );
+
+        let mut doc = old.clone();
+        doc.apply_patch(&patch).unwrap();
+        assert_eq!(doc, new);
+    }
+
+    #[test]
+    fn test_diff_round_trip() {
+        let pairs = [
+            ("[1, 2, 3, 4]", "[1]"),
+            ("[]", "[{\"a\": [true]}, 2]"),
+            (
+                "{\"a\": [1, {\"b\": 2}]}",
+                "{\"a\": [1, {\"b\": 3, \"c\": 4}, 5]}",
+            ),
+            ("{\"a\": 1}", "[\"a\", 1]"),
+            ("{}", "{}"),
+        ];
+        for (old, new) in pairs {
+            let (old, new) = (parse(old), parse(new));
+            let mut doc = old.clone();
+            doc.apply_patch(&JValue::diff(&old, &new)).unwrap();
+
+            assert_eq!(doc, new, "{} -> {}", old, new);
+        }
+        assert_eq!(JValue::diff(&parse("{}"), &parse("{}")).to_string(), "[]");
+        assert_eq!(
+            JValue::diff(&parse("{}"), &parse("[]")).to_string(),
+            "[{\"op\":\"replace\",\"path\":\"\",\"value\":[]}]"
+        );
+    }
+}
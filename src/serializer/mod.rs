@@ -0,0 +1,950 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Write};
+use std::io;
+
+use indexmap::{IndexMap, IndexSet};
+
+use crate::parser::{Comments, JValue};
+use crate::tokenizer::Number;
+use crate::value::escape_pointer_token;
+
+type KeyCmp<'a> = &'a dyn Fn(&str, &str) -> Ordering;
+
+#[derive(Default)]
+struct Style<'a> {
+    indent: Option<String>,
+    cmp: Option<KeyCmp<'a>>,
+}
+
+/// What the pretty printer writes per level of nesting. A plain number of
+/// spaces converts into one, so `to_string_pretty(2)` works as it reads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    /// One tab per level.
+    Tabs,
+    /// Any string, written as it is once per level.
+    Custom(String),
+}
+
+impl Indent {
+    fn unit(&self) -> String {
+        match self {
+            Indent::Spaces(n) => " ".repeat(*n),
+            Indent::Tabs => "\t".to_string(),
+            Indent::Custom(s) => s.clone(),
+        }
+    }
+}
+
+impl From<usize> for Indent {
+    fn from(n: usize) -> Self {
+        Indent::Spaces(n)
+    }
+}
+
+/// Why a value has no RFC 8785 canonical form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanonicalError {
+    /// `NaN`, an infinity, or a number too large for an `f64`.
+    NonFiniteNumber(Number),
+}
+
+impl Display for CanonicalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanonicalError::NonFiniteNumber(n) => {
+                write!(f, "number {} has no canonical form", n)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CanonicalError {}
+
+/// Why a value cannot be laid out as CSV.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CsvError {
+    NotAnArray,
+    /// The element at this index is not an object.
+    NotAnObject(usize),
+}
+
+impl Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CsvError::NotAnArray => write!(f, "value is not an array"),
+            CsvError::NotAnObject(i) => write!(f, "element {} is not an object", i),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+impl Display for JValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self, &Style::default(), 0)
+    }
+}
+
+impl JValue {
+    /// Serializes with object keys ordered by `cmp`. The tree itself is left untouched.
+    pub fn to_string_ordered_by<F>(&self, cmp: F) -> String
+    where
+        F: Fn(&str, &str) -> Ordering,
+    {
+        let style = Style {
+            cmp: Some(&cmp),
+            ..Default::default()
+        };
+        let mut s = String::new();
+        write_value(&mut s, self, &style, 0).unwrap();
+        s
+    }
+
+    /// Serializes with the keys of every object in lexicographic (byte) order,
+    /// giving the same text for trees that differ only in key order.
+    pub fn to_string_sorted(&self) -> String {
+        self.to_string_ordered_by(|a, b| a.cmp(b))
+    }
+
+    /// Serializes with the listed keys first, in the given order. Keys not in
+    /// `keys` follow in their insertion order.
+    pub fn to_string_with_key_order(&self, keys: &[&str]) -> String {
+        let rank = |k: &str| keys.iter().position(|&x| x == k).unwrap_or(keys.len());
+        self.to_string_ordered_by(|a, b| rank(a).cmp(&rank(b)))
+    }
+
+    /// Serializes with one entry per line, nested one `indent` per level.
+    pub fn to_string_pretty(&self, indent: impl Into<Indent>) -> String {
+        let mut s = String::new();
+        write_value(&mut s, self, &Style::pretty(indent), 0).unwrap();
+        s
+    }
+
+    /// Like `to_string_pretty`, with the comments that `Parser::comments`
+    /// recorded put back: leading ones on the lines before their value,
+    /// trailing ones after it on its last line. Comments of paths the value
+    /// does not have are dropped.
+    pub fn to_string_pretty_with_comments(
+        &self,
+        indent: impl Into<Indent>,
+        comments: &IndexMap<String, Comments>,
+    ) -> String {
+        let mut w = CommentWriter {
+            out: String::new(),
+            comments,
+            indent: indent.into().unit(),
+            path: String::new(),
+        };
+        let root = comments.get("");
+        for c in root.map_or(&[][..], |c| &c.leading) {
+            w.out.push_str(c);
+            w.out.push('\n');
+        }
+        w.value(self, 0);
+        w.trailing(root);
+        w.out
+    }
+
+    /// Serializes per RFC 8785 (JCS): no whitespace, keys sorted by their
+    /// UTF-16 code units, and numbers written the way ECMAScript prints an
+    /// `f64`, so `4.50` becomes `4.5` and `1E30` becomes `1e+30`. Numbers lose
+    /// any precision beyond an `f64`.
+    pub fn to_canonical_string(&self) -> Result<String, CanonicalError> {
+        let mut s = String::new();
+        write_canonical(&mut s, self)?;
+        Ok(s)
+    }
+
+    /// Renders the value as an indented tree, one node per line, each with
+    /// its key or index, its type and, for scalars, its value. For reading
+    /// while debugging; the format may change.
+    pub fn tree_string(&self) -> String {
+        let mut s = String::new();
+        write_tree_node(&mut s, self);
+        write_tree_children(&mut s, self, &mut String::new());
+        s
+    }
+
+    /// Lays out an array of objects as CSV (RFC 4180): a header row with every
+    /// key that occurs, in order of first appearance, then one row per object.
+    /// Missing keys and `null` give empty cells, strings are written as they
+    /// are, and arrays and objects as compact JSON. Rows end with `\n`.
+    pub fn to_csv(&self) -> Result<String, CsvError> {
+        let JValue::Array(rows) = self else {
+            return Err(CsvError::NotAnArray);
+        };
+        let mut columns = IndexSet::new();
+        for (i, row) in rows.iter().enumerate() {
+            let JValue::Object(m) = row else {
+                return Err(CsvError::NotAnObject(i));
+            };
+            columns.extend(m.keys().map(String::as_str));
+        }
+
+        let mut s = String::new();
+        write_csv_row(&mut s, columns.iter().map(|&k| Cow::Borrowed(k)));
+        for row in rows {
+            let cells = columns.iter().map(|&k| match row.get(k) {
+                None | Some(JValue::Null) => Cow::Borrowed(""),
+                Some(JValue::String(v)) => Cow::Borrowed(v.as_str()),
+                Some(v) => Cow::Owned(v.to_string()),
+            });
+            write_csv_row(&mut s, cells);
+        }
+        Ok(s)
+    }
+
+    /// Same output as `to_string`, written straight to `w` as it is produced
+    /// rather than collected into a `String` first. Wrap unbuffered writers
+    /// such as sockets in a `BufWriter`.
+    pub fn write_to<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        write_io(w, self, &Style::default())
+    }
+
+    /// Same layout as `to_string_pretty`, written straight to `w`.
+    pub fn write_pretty<W: io::Write>(
+        &self,
+        w: &mut W,
+        indent: impl Into<Indent>,
+    ) -> io::Result<()> {
+        write_io(w, self, &Style::pretty(indent))
+    }
+}
+
+fn write_io<W: io::Write>(w: &mut W, value: &JValue, style: &Style) -> io::Result<()> {
+    let mut adapter = IoAdapter {
+        inner: w,
+        error: None,
+    };
+    match write_value(&mut adapter, value, style, 0) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(adapter
+            .error
+            .unwrap_or_else(|| io::Error::other("formatter error"))),
+    }
+}
+
+impl Style<'_> {
+    fn pretty(indent: impl Into<Indent>) -> Self {
+        Self {
+            indent: Some(indent.into().unit()),
+            ..Default::default()
+        }
+    }
+}
+
+// forwards fmt::Write to an io::Write, keeping the io error that fmt::Error drops
+struct IoAdapter<'a, W: io::Write> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
+
+fn write_newline<W: Write>(out: &mut W, style: &Style, depth: usize) -> fmt::Result {
+    if let Some(indent) = &style.indent {
+        out.write_char('\n')?;
+        for _ in 0..depth {
+            out.write_str(indent)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_value<W: Write>(out: &mut W, value: &JValue, style: &Style, depth: usize) -> fmt::Result {
+    match value {
+        JValue::Null => out.write_str("null"),
+        JValue::Bool(b) => write!(out, "{}", b),
+        JValue::Number(n) => write!(out, "{}", n),
+        JValue::String(s) => write_string(out, s),
+        JValue::Array(arr) if arr.is_empty() => out.write_str("[]"),
+        JValue::Array(arr) => {
+            out.write_char('[')?;
+            for (i, v) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_newline(out, style, depth + 1)?;
+                write_value(out, v, style, depth + 1)?;
+            }
+            write_newline(out, style, depth)?;
+            out.write_char(']')
+        }
+        JValue::Object(m) if m.is_empty() => out.write_str("{}"),
+        JValue::Object(m) => {
+            let mut entries = m.iter().collect::<Vec<_>>();
+            if let Some(cmp) = style.cmp {
+                // stable, so keys comparing equal keep their insertion order
+                entries.sort_by(|(a, _), (b, _)| cmp(a, b));
+            }
+
+            out.write_char('{')?;
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_newline(out, style, depth + 1)?;
+                write_string(out, k)?;
+                out.write_char(':')?;
+                if style.indent.is_some() {
+                    out.write_char(' ')?;
+                }
+                write_value(out, v, style, depth + 1)?;
+            }
+            write_newline(out, style, depth)?;
+            out.write_char('}')
+        }
+    }
+}
+
+pub(crate) fn write_string<W: Write>(out: &mut W, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            '\u{08}' => out.write_str("\\b")?,
+            '\u{0c}' => out.write_str("\\f")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+// pretty-prints a value with the recorded comments around its members
+struct CommentWriter<'a> {
+    out: String,
+    comments: &'a IndexMap<String, Comments>,
+    indent: String,
+    // JSON Pointer of the value being written
+    path: String,
+}
+
+impl CommentWriter<'_> {
+    fn value(&mut self, value: &JValue, depth: usize) {
+        match value {
+            JValue::Array(arr) if !arr.is_empty() => {
+                self.out.push('[');
+                for (i, v) in arr.iter().enumerate() {
+                    self.member(&i.to_string(), None, v, i + 1 == arr.len(), depth + 1);
+                }
+                self.close(']', depth);
+            }
+            JValue::Object(m) if !m.is_empty() => {
+                self.out.push('{');
+                for (i, (k, v)) in m.iter().enumerate() {
+                    let token = escape_pointer_token(k);
+                    self.member(&token, Some(k), v, i + 1 == m.len(), depth + 1);
+                }
+                self.close('}', depth);
+            }
+            _ => write_value(&mut self.out, value, &Style::default(), 0).unwrap(),
+        }
+    }
+
+    fn member(&mut self, token: &str, key: Option<&str>, value: &JValue, last: bool, depth: usize) {
+        let len = self.path.len();
+        self.path.push('/');
+        self.path.push_str(token);
+        let comments = self.comments.get(&self.path);
+
+        for c in comments.map_or(&[][..], |c| &c.leading) {
+            self.newline(depth);
+            self.out.push_str(c);
+        }
+        self.newline(depth);
+        if let Some(key) = key {
+            write_string(&mut self.out, key).unwrap();
+            self.out.push_str(": ");
+        }
+        self.value(value, depth);
+        if !last {
+            self.out.push(',');
+        }
+        self.trailing(comments);
+        self.path.truncate(len);
+    }
+
+    fn trailing(&mut self, comments: Option<&Comments>) {
+        for c in comments.map_or(&[][..], |c| &c.trailing) {
+            self.out.push(' ');
+            self.out.push_str(c);
+        }
+    }
+
+    fn close(&mut self, bracket: char, depth: usize) {
+        self.newline(depth);
+        self.out.push(bracket);
+    }
+
+    fn newline(&mut self, depth: usize) {
+        self.out.push('\n');
+        for _ in 0..depth {
+            self.out.push_str(&self.indent);
+        }
+    }
+}
+
+fn write_csv_row<'a>(out: &mut String, cells: impl Iterator<Item = Cow<'a, str>>) {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if cell.contains([',', '"', '\r', '\n']) {
+            out.push('"');
+            out.push_str(&cell.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(&cell);
+        }
+    }
+    out.push('\n');
+}
+
+// the type and, for scalars, the value of a tree node, ending the line
+fn write_tree_node(out: &mut String, value: &JValue) {
+    out.push_str(value.type_name());
+    match value {
+        JValue::Null => {}
+        JValue::Array(arr) => out.push_str(&format!(" ({})", arr.len())),
+        JValue::Object(m) => out.push_str(&format!(" ({})", m.len())),
+        scalar => out.push_str(&format!(" {}", scalar)),
+    }
+    out.push('\n');
+}
+
+// `prefix` holds the guide lines of the levels above
+fn write_tree_children(out: &mut String, value: &JValue, prefix: &mut String) {
+    let children: Vec<(String, &JValue)> = match value {
+        JValue::Array(arr) => arr
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (format!("[{}]", i), v))
+            .collect(),
+        JValue::Object(m) => m.iter().map(|(k, v)| (format!("{:?}", k), v)).collect(),
+        _ => return,
+    };
+    let count = children.len();
+    for (i, (label, child)) in children.into_iter().enumerate() {
+        let last = i + 1 == count;
+        out.push_str(prefix);
+        out.push_str(if last { "└── " } else { "├── " });
+        out.push_str(&label);
+        out.push_str(": ");
+        write_tree_node(out, child);
+
+        let len = prefix.len();
+        prefix.push_str(if last { "    " } else { "│   " });
+        write_tree_children(out, child, prefix);
+        prefix.truncate(len);
+    }
+}
+
+fn write_canonical(out: &mut String, value: &JValue) -> Result<(), CanonicalError> {
+    match value {
+        JValue::Number(n) => {
+            let v = n.as_f64();
+            if !v.is_finite() {
+                return Err(CanonicalError::NonFiniteNumber(n.clone()));
+            }
+            out.push_str(&es_number(v));
+        }
+        JValue::Array(arr) => {
+            out.push('[');
+            for (i, v) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(out, v)?;
+            }
+            out.push(']');
+        }
+        JValue::Object(m) => {
+            let mut entries = m.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            out.push('{');
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(out, k).unwrap();
+                out.push(':');
+                write_canonical(out, v)?;
+            }
+            out.push('}');
+        }
+        scalar => write_value(out, scalar, &Style::default(), 0).unwrap(),
+    }
+    Ok(())
+}
+
+// ECMAScript's Number::toString for a finite value (ECMA-262, 6.1.6.1.20)
+fn es_number(v: f64) -> String {
+    if v == 0.0 {
+        return "0".to_string();
+    }
+    // `{:e}` gives the shortest round-tripping digits, as ECMAScript requires
+    let sci = format!("{:e}", v.abs());
+    let (mantissa, exponent) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    // the value is 0.digits * 10^n
+    let n = exponent.parse::<i32>().unwrap_or(0) + 1;
+
+    let mut s = if v < 0.0 {
+        "-".to_string()
+    } else {
+        String::new()
+    };
+    if k <= n && n <= 21 {
+        s.push_str(&digits);
+        s.push_str(&"0".repeat((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        s.push_str(&digits[..n as usize]);
+        s.push('.');
+        s.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        s.push_str("0.");
+        s.push_str(&"0".repeat(-n as usize));
+        s.push_str(&digits);
+    } else {
+        s.push_str(&digits[..1]);
+        if k > 1 {
+            s.push('.');
+            s.push_str(&digits[1..]);
+        }
+        s.push_str(&format!(
+            "e{}{}",
+            if n > 0 { "+" } else { "-" },
+            (n - 1).abs()
+        ));
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use indexmap::IndexMap;
+
+    // generates arbitrary values from a fixed xorshift seed, so failures
+    // reproduce on every run
+    struct Gen(u64);
+
+    impl Gen {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn string(&mut self) -> String {
+            const CHARS: &[char] = &[
+                'a', 'Z', '0', ' ', '"', '\\', '/', '\n', '\t', '\u{1}', '\u{1f}', '\u{7f}', 'é',
+                '€', '\u{2028}', '😀',
+            ];
+            (0..self.below(8))
+                .map(|_| CHARS[self.below(CHARS.len() as u64) as usize])
+                .collect()
+        }
+
+        fn number(&mut self) -> Number {
+            let int = match self.below(3) {
+                0 => self.next() as i32,
+                _ => self.below(2000) as i32 - 1000,
+            };
+            // fractions with few enough bits to be exact in an f32
+            let frac = (self.below(2) == 0).then(|| (self.next() >> 40) as f32 / (1 << 24) as f32);
+            let exponent = (self.below(3) == 0).then(|| self.below(41) as i32 - 20);
+            match self.below(2) {
+                0 => Number::new(int, frac, exponent),
+                // parsed from text, so it carries its source
+                _ => Number::new(int, frac, exponent)
+                    .to_string()
+                    .parse()
+                    .unwrap(),
+            }
+        }
+
+        fn value(&mut self, depth: usize) -> JValue {
+            let kinds = if depth == 0 { 4 } else { 6 };
+            match self.below(kinds) {
+                0 => JValue::Null,
+                1 => JValue::Bool(self.below(2) == 0),
+                2 => JValue::String(self.string()),
+                3 => JValue::Number(self.number()),
+                4 => self.array(depth - 1),
+                _ => self.object(depth - 1),
+            }
+        }
+
+        fn array(&mut self, depth: usize) -> JValue {
+            JValue::Array((0..self.below(5)).map(|_| self.value(depth)).collect())
+        }
+
+        fn object(&mut self, depth: usize) -> JValue {
+            let mut m = IndexMap::new();
+            for _ in 0..self.below(5) {
+                m.insert(self.string(), self.value(depth));
+            }
+            JValue::Object(m)
+        }
+
+        // documents must be an object or an array at the top
+        fn document(&mut self) -> JValue {
+            match self.below(2) {
+                0 => self.array(4),
+                _ => self.object(4),
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_property() {
+        let mut gen = Gen(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let value = gen.document();
+            for text in [value.to_string(), value.to_string_pretty(2)] {
+                let parsed = Parser::new(text.clone())
+                    .parse()
+                    .unwrap_or_else(|e| panic!("{} on {:?}", e, text));
+                assert!(
+                    parsed.semantic_eq(&value),
+                    "{:?} became {:?}",
+                    value,
+                    parsed
+                );
+                // what was parsed serializes back to the same text
+                assert_eq!(parsed.to_string(), value.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_compact() {
+        let input = "{\"foo\": [1, 2.5, \"bar\"], \"active\": true, \"none\": null}".to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let expected = "{\"foo\":[1,2.5,\"bar\"],\"active\":true,\"none\":null}";
+
+        assert_eq!(value.to_string(), expected);
+    }
+
+    #[test]
+    fn test_number_round_trip() {
+        let input = "[3.141592653589793,-0.1,1e-7,12345678.9]";
+        let value = Parser::new(input.to_string()).parse().unwrap();
+
+        assert_eq!(value.to_string(), input);
+    }
+
+    #[test]
+    fn test_escape() {
+        let value = JValue::String("a\"b\\c\nd\u{01}".to_string());
+        let expected = "\"a\\\"b\\\\c\\nd\\u0001\"";
+
+        assert_eq!(value.to_string(), expected);
+    }
+
+    #[test]
+    fn test_escape_round_trip() {
+        let input = r#"{"a\"b":"c\\d\n\u0001\/"}"#;
+        let value = Parser::new(input.to_string()).parse().unwrap();
+
+        assert_eq!(value.to_string(), r#"{"a\"b":"c\\d\n\u0001/"}"#);
+    }
+
+    #[test]
+    fn test_key_order() {
+        let input = "{\"name\": \"x\", \"extra\": 1, \"id\": 2, \"kind\": {\"b\": 1, \"id\": 2}}"
+            .to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let expected = "{\"id\":2,\"kind\":{\"id\":2,\"b\":1},\"name\":\"x\",\"extra\":1}";
+
+        assert_eq!(
+            value.to_string_with_key_order(&["id", "kind", "name"]),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_ordered_by_does_not_mutate() {
+        let input = "{\"b\": 1, \"c\": 2, \"a\": 3}".to_string();
+        let value = Parser::new(input).parse().unwrap();
+
+        assert_eq!(
+            value.to_string_ordered_by(|a, b| b.cmp(a)),
+            "{\"c\":2,\"b\":1,\"a\":3}"
+        );
+        assert_eq!(value.to_string(), "{\"b\":1,\"c\":2,\"a\":3}");
+    }
+
+    #[test]
+    fn test_pretty() {
+        let input = "{\"a\": [1, {}], \"b\": {\"c\": null}, \"d\": []}".to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let expected = "{\n  \"a\": [\n    1,\n    {}\n  ],\n  \"b\": {\n    \"c\": null\n  },\n  \"d\": []\n}";
+
+        assert_eq!(value.to_string_pretty(2), expected);
+    }
+
+    #[test]
+    fn test_pretty_indent() {
+        let value = Parser::new("{\"a\": [1], \"b\": {}}".to_string())
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            value.to_string_pretty(Indent::Tabs),
+            "{\n\t\"a\": [\n\t\t1\n\t],\n\t\"b\": {}\n}"
+        );
+        assert_eq!(
+            value.to_string_pretty(Indent::Custom("| ".to_string())),
+            "{\n| \"a\": [\n| | 1\n| ],\n| \"b\": {}\n}"
+        );
+        assert_eq!(
+            value.to_string_pretty(Indent::Spaces(3)),
+            value.to_string_pretty(3)
+        );
+    }
+
+    #[test]
+    fn test_write_pretty() {
+        let input = "[{\"foo\": \"bar\", \"n\": [1, 2]}, true]".to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let mut buf = Vec::<u8>::new();
+        value.write_pretty(&mut buf, 4).unwrap();
+
+        assert_eq!(String::from_utf8(buf).unwrap(), value.to_string_pretty(4));
+    }
+
+    #[test]
+    fn test_write_to() {
+        let input = "{\"s\": \"a\\\"b\", \"n\": [1.50, -2e3], \"o\": {}}".to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let mut buf = Vec::<u8>::new();
+        value.write_to(&mut buf).unwrap();
+
+        assert_eq!(String::from_utf8(buf).unwrap(), value.to_string());
+    }
+
+    #[test]
+    fn test_write_to_error() {
+        struct Full;
+
+        impl io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::StorageFull, "full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let err = JValue::Array(vec![JValue::Null])
+            .write_to(&mut Full)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+    }
+
+    #[test]
+    fn test_sorted() {
+        let input =
+            "{\"b\": {\"z\": 1, \"a\": [{\"y\": 2, \"x\": 3}]}, \"a\": null, \"B\": 0}".to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let expected = "{\"B\":0,\"a\":null,\"b\":{\"a\":[{\"x\":3,\"y\":2}],\"z\":1}}";
+
+        assert_eq!(value.to_string_sorted(), expected);
+    }
+
+    #[test]
+    fn test_canonical() {
+        // RFC 8785, section 3.2.2
+        let input = r#"{
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+            "literals": [null, true, false]
+        }"#;
+        let value = Parser::new(input.to_string()).parse().unwrap();
+        let expected = r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#;
+
+        assert_eq!(value.to_canonical_string().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_canonical_key_order() {
+        // RFC 8785, section 3.2.3: sorted by UTF-16 code units, so the emoji's
+        // surrogates sort before U+FB33
+        let input = r#"{
+            "\u20ac": "Euro Sign",
+            "\r": "Carriage Return",
+            "\ufb33": "Hebrew Letter Dalet With Dagesh",
+            "1": "One",
+            "\ud83d\ude00": "Emoji: Grinning Face",
+            "\u0080": "Control",
+            "\u00f6": "Latin Small Letter O With Diaeresis"
+        }"#;
+        let value = Parser::new(input.to_string()).parse().unwrap();
+        let canonical = value.to_canonical_string().unwrap();
+        let reparsed = Parser::new(canonical).parse().unwrap();
+        let JValue::Object(m) = &reparsed else {
+            panic!("expected an object");
+        };
+
+        assert_eq!(
+            m.values().map(|v| v.to_string()).collect::<Vec<_>>(),
+            [
+                "\"Carriage Return\"",
+                "\"One\"",
+                "\"Control\"",
+                "\"Latin Small Letter O With Diaeresis\"",
+                "\"Euro Sign\"",
+                "\"Emoji: Grinning Face\"",
+                "\"Hebrew Letter Dalet With Dagesh\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_es_number() {
+        // RFC 8785, appendix B
+        let cases = [
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+        ];
+        for (bits, expected) in cases {
+            assert_eq!(es_number(f64::from_bits(bits)), expected, "{:#x}", bits);
+        }
+    }
+
+    #[test]
+    fn test_canonical_non_finite() {
+        let options = crate::options::ParserOptions {
+            allow_non_finite: true,
+            ..Default::default()
+        };
+        for input in ["[NaN]", "[1e400]"] {
+            let value = Parser::with_options(input.to_string(), options.clone())
+                .parse()
+                .unwrap();
+
+            assert!(matches!(
+                value.to_canonical_string(),
+                Err(CanonicalError::NonFiniteNumber(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_tree_string() {
+        let input = "{\"a\": [1, {\"b\": null}], \"c\": \"d\", \"e\": {}}".to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let expected = "\
+object (3)
+├── \"a\": array (2)
+│   ├── [0]: number 1
+│   └── [1]: object (1)
+│       └── \"b\": null
+├── \"c\": string \"d\"
+└── \"e\": object (0)
+";
+
+        assert_eq!(value.tree_string(), expected);
+        assert_eq!(JValue::Bool(true).tree_string(), "bool true\n");
+    }
+
+    #[test]
+    fn test_pretty_with_comments() {
+        let input = "// the config
+{
+  // where to listen
+  \"port\": 8080, // default
+  \"hosts\": [
+    \"a\", /* primary */
+    \"b\"
+  ],
+  \"empty\": {} /* nothing */
+} // end";
+        let options = crate::options::ParserOptions {
+            record_comments: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(input.to_string(), options.clone());
+        let value = parser.parse().unwrap();
+        let output = value.to_string_pretty_with_comments(2, parser.comments());
+
+        assert_eq!(output, input);
+        // without comments it is plain pretty output
+        assert_eq!(
+            value.to_string_pretty_with_comments(2, &IndexMap::new()),
+            value.to_string_pretty(2)
+        );
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let input = r#"[
+            {"id": 1, "name": "Ann", "tags": ["a", "b"]},
+            {"id": 2, "name": "Bob, Jr.", "email": null},
+            {"name": "say \"hi\"", "email": "c@x.org", "id": 3.5}
+        ]"#;
+        let value = Parser::new(input.to_string()).parse().unwrap();
+        let expected = "id,name,tags,email\n\
+                        1,Ann,\"[\"\"a\"\",\"\"b\"\"]\",\n\
+                        2,\"Bob, Jr.\",,\n\
+                        3.5,\"say \"\"hi\"\"\",,c@x.org\n";
+
+        assert_eq!(value.to_csv().unwrap(), expected);
+        assert_eq!(JValue::Array(vec![]).to_csv().unwrap(), "\n");
+    }
+
+    #[test]
+    fn test_to_csv_errors() {
+        let value = Parser::new("[{}, 1]".to_string()).parse().unwrap();
+
+        assert_eq!(value.to_csv(), Err(CsvError::NotAnObject(1)));
+        assert_eq!(JValue::Null.to_csv(), Err(CsvError::NotAnArray));
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::Display;
+
+use indexmap::IndexMap;
+
+use crate::parser::JValue;
+use crate::value::escape_pointer_token;
+
+/// The expected layout of a value, checked by `JValue::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// Matches every value.
+    Any,
+    Null,
+    Bool,
+    String,
+    Number,
+    /// An array whose elements all match the shape.
+    Array(Box<Shape>),
+    /// An object that has every listed key, with a value matching its shape.
+    /// Other keys are allowed.
+    Object(IndexMap<String, Shape>),
+}
+
+impl Shape {
+    /// Name of the JSON type the shape expects, as `JValue::type_name` gives it.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Shape::Any => "any",
+            Shape::Null => "null",
+            Shape::Bool => "bool",
+            Shape::String => "string",
+            Shape::Number => "number",
+            Shape::Array(_) => "array",
+            Shape::Object(_) => "object",
+        }
+    }
+}
+
+/// A mismatch found by `JValue::validate`. Paths are JSON Pointers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    TypeMismatch {
+        path: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// A required key is absent; the path is where it should be.
+    MissingKey { path: String },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::TypeMismatch {
+                path,
+                expected,
+                found,
+            } => write!(f, "expected {}, got {} at {:?}", expected, found, path),
+            ValidationError::MissingKey { path } => write!(f, "missing key at {:?}", path),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl JValue {
+    /// Checks the value against `shape`, reporting every mismatch rather than
+    /// stopping at the first.
+    pub fn validate(&self, shape: &Shape) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        check(self, shape, &mut String::new(), &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn check(value: &JValue, shape: &Shape, path: &mut String, errors: &mut Vec<ValidationError>) {
+    let len = path.len();
+    match (shape, value) {
+        (Shape::Any, _)
+        | (Shape::Null, JValue::Null)
+        | (Shape::Bool, JValue::Bool(_))
+        | (Shape::String, JValue::String(_))
+        | (Shape::Number, JValue::Number(_)) => {}
+        (Shape::Array(element), JValue::Array(arr)) => {
+            for (i, v) in arr.iter().enumerate() {
+                path.push_str(&format!("/{}", i));
+                check(v, element, path, errors);
+                path.truncate(len);
+            }
+        }
+        (Shape::Object(members), JValue::Object(m)) => {
+            for (k, member) in members {
+                path.push_str(&format!("/{}", escape_pointer_token(k)));
+                match m.get(k) {
+                    Some(v) => check(v, member, path, errors),
+                    None => errors.push(ValidationError::MissingKey { path: path.clone() }),
+                }
+                path.truncate(len);
+            }
+        }
+        _ => errors.push(ValidationError::TypeMismatch {
+            path: path.clone(),
+            expected: shape.type_name(),
+            found: value.type_name(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn user() -> Shape {
+        let mut address = IndexMap::new();
+        address.insert("city".to_string(), Shape::String);
+        address.insert("zip".to_string(), Shape::Any);
+
+        let mut m = IndexMap::new();
+        m.insert("name".to_string(), Shape::String);
+        m.insert("age".to_string(), Shape::Number);
+        m.insert("tags".to_string(), Shape::Array(Box::new(Shape::String)));
+        m.insert("address".to_string(), Shape::Object(address));
+        Shape::Object(m)
+    }
+
+    #[test]
+    fn test_valid() {
+        let input = "{\"name\": \"bob\", \"age\": 42, \"tags\": [\"a\", \"b\"], \
+                     \"address\": {\"city\": \"Oslo\", \"zip\": null}, \"extra\": true}";
+        let value = Parser::new(input.to_string()).parse().unwrap();
+
+        assert_eq!(value.validate(&user()), Ok(()));
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = "{\"name\": 7, \"tags\": [\"a\", false, \"c\", null], \"address\": {}}";
+        let value = Parser::new(input.to_string()).parse().unwrap();
+        let errors = value.validate(&user()).unwrap_err();
+
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            [
+                "expected string, got number at \"/name\"",
+                "missing key at \"/age\"",
+                "expected string, got bool at \"/tags/1\"",
+                "expected string, got null at \"/tags/3\"",
+                "missing key at \"/address/city\"",
+                "missing key at \"/address/zip\"",
+            ]
+        );
+        assert_eq!(
+            JValue::Null.validate(&user()),
+            Err(vec![ValidationError::TypeMismatch {
+                path: "".to_string(),
+                expected: "object",
+                found: "null",
+            }])
+        );
+    }
+}
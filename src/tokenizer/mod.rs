@@ -1,8 +1,14 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fmt::Display;
-use std::iter::Peekable;
-use std::vec::IntoIter;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::options::ParserOptions;
+use crate::serializer::write_string;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum JToken {
     LeftBrace,      // {
     RightBrace,     // }
@@ -14,13 +20,70 @@ pub enum JToken {
     Bool(bool),     // true, false
     Number(Number), // number
     String(String), // "string"
+    // number text as written, emitted instead of `Number` when
+    // `ParserOptions::raw_numbers` is set
+    RawNumber(String),
+    // a bare word that is not a keyword, only emitted when
+    // `ParserOptions::allow_unquoted_keys` is set
+    Ident(String),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Payload-free counterpart of `JToken`, for cheap lookahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Collon,
+    Comma,
+    Null,
+    Bool,
+    Number,
+    String,
+    Ident,
+}
+
+impl JToken {
+    pub fn kind(&self) -> TokenKind {
+        match self {
+            JToken::LeftBrace => TokenKind::LeftBrace,
+            JToken::RightBrace => TokenKind::RightBrace,
+            JToken::LeftBracket => TokenKind::LeftBracket,
+            JToken::RightBracket => TokenKind::RightBracket,
+            JToken::Collon => TokenKind::Collon,
+            JToken::Comma => TokenKind::Comma,
+            JToken::Null => TokenKind::Null,
+            JToken::Bool(_) => TokenKind::Bool,
+            JToken::Number(_) | JToken::RawNumber(_) => TokenKind::Number,
+            JToken::String(_) => TokenKind::String,
+            JToken::Ident(_) => TokenKind::Ident,
+        }
+    }
+}
+
+/// Byte range `start..end` of a token in the original input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Number {
-    int: i32,
-    frac: Option<f32>,
-    exponent: Option<i32>,
+    pub(crate) int: i32,
+    pub(crate) frac: Option<f32>,
+    pub(crate) exponent: Option<i32>,
+    // source text as written, kept by the tokenizer
+    pub(crate) raw: Option<String>,
+    // NaN or ±Infinity, only produced when `allow_non_finite` is set
+    pub(crate) non_finite: Option<f64>,
 }
 
 impl Number {
@@ -29,6 +92,430 @@ impl Number {
             int,
             frac,
             exponent,
+            raw: None,
+            non_finite: None,
+        }
+    }
+
+    fn new_non_finite(v: f64) -> Self {
+        Self {
+            non_finite: Some(v),
+            ..Self::new(0, None, None)
+        }
+    }
+
+    /// False for the `NaN`, `Infinity` and `-Infinity` literals.
+    pub fn is_finite(&self) -> bool {
+        self.non_finite.is_none()
+    }
+
+    /// Whether the number is written as a plain integer, without a fraction or
+    /// an exponent. `5` is an integer; `5.0` and `5e0` are not, even though
+    /// their values are whole.
+    pub fn is_integer(&self) -> bool {
+        // the parts of a number beyond `i32` are an approximation in
+        // scientific form, so the text decides when there is one
+        let plain = match &self.raw {
+            Some(raw) => !raw.contains(['.', 'e', 'E']),
+            None => self.frac.is_none() && self.exponent.is_none(),
+        };
+        self.is_finite() && plain
+    }
+
+    /// The value as the nearest `f64`. Magnitudes beyond its range saturate:
+    /// `1e400` becomes infinity and `1e-400` zero, keeping the sign. The
+    /// non-finite literals keep their value. See `checked_as_f64`.
+    pub fn as_f64(&self) -> f64 {
+        if let Some(v) = self.non_finite {
+            return v;
+        }
+        self.text().parse().unwrap_or(f64::NAN)
+    }
+
+    /// Like `as_f64`, but `None` where the `f64` would not stand for the
+    /// number: when its magnitude overflows to infinity or underflows to zero,
+    /// and for the non-finite literals.
+    pub fn checked_as_f64(&self) -> Option<f64> {
+        if !self.is_finite() {
+            return None;
+        }
+        let v = self.as_f64();
+        let text = self.text();
+        let mantissa = text.split(['e', 'E']).next().unwrap_or_default();
+        let nonzero = mantissa.bytes().any(|b| matches!(b, b'1'..=b'9'));
+        (v.is_finite() && (v != 0.0 || !nonzero)).then_some(v)
+    }
+
+    /// The significand in scientific notation, with one nonzero digit before
+    /// the point, so `-1.25` for `-0.0125e3`. Zero for any zero, and the value
+    /// itself for the non-finite literals. See `exponent_value`.
+    pub fn mantissa(&self) -> f64 {
+        if let Some(v) = self.non_finite {
+            return v;
+        }
+        let (negative, digits, _) = self.scientific();
+        if digits.is_empty() {
+            return 0.0;
+        }
+        let mantissa = format!("{}.{}", &digits[..1], &digits[1..])
+            .parse::<f64>()
+            .unwrap_or_default();
+        if negative {
+            -mantissa
+        } else {
+            mantissa
+        }
+    }
+
+    /// The power of ten that goes with `mantissa`, so the number is
+    /// `mantissa * 10^exponent_value`: `1` for `-0.0125e3`. Zero for any zero
+    /// and for the non-finite literals.
+    pub fn exponent_value(&self) -> i64 {
+        match self.non_finite {
+            Some(_) => 0,
+            None => self.scientific().2,
+        }
+    }
+
+    /// The same value in a canonical form, so that `1E2`, `100` and `100.0`
+    /// all become `100`:
+    ///
+    /// - no leading `+`, and no sign on zero;
+    /// - no leading zeros, and no trailing zeros after the point;
+    /// - written without an exponent when the power of ten of its first
+    ///   significant digit is from -7 to 20, as JavaScript prints numbers;
+    /// - otherwise as one digit, the remaining digits after a point if there
+    ///   are any, then `e` and the exponent without a `+`, as in `1.5e-9`.
+    ///
+    /// The non-finite literals are left as they are.
+    pub fn normalized(&self) -> Number {
+        if !self.is_finite() {
+            return self.clone();
+        }
+        let (negative, digits, exponent) = self.scientific();
+        if digits.is_empty() {
+            return "0".parse().unwrap_or_else(|_| self.clone());
+        }
+        let mut text = String::from(if negative { "-" } else { "" });
+        match exponent {
+            0..=20 => {
+                let int_len = exponent as usize + 1;
+                if digits.len() <= int_len {
+                    text.push_str(&digits);
+                    text.push_str(&"0".repeat(int_len - digits.len()));
+                } else {
+                    text.push_str(&digits[..int_len]);
+                    text.push('.');
+                    text.push_str(&digits[int_len..]);
+                }
+            }
+            -7..=-1 => {
+                text.push_str("0.");
+                text.push_str(&"0".repeat((-exponent - 1) as usize));
+                text.push_str(&digits);
+            }
+            _ => {
+                text.push_str(&digits[..1]);
+                if digits.len() > 1 {
+                    text.push('.');
+                    text.push_str(&digits[1..]);
+                }
+                text.push('e');
+                text.push_str(&exponent.to_string());
+            }
+        }
+        text.parse().unwrap_or_else(|_| self.clone())
+    }
+
+    // the finite number in scientific notation: its sign, its significant
+    // digits without leading or trailing zeros (none for zero), and the power
+    // of ten of the first of them
+    pub(crate) fn scientific(&self) -> (bool, String, i64) {
+        scientific(&self.text())
+    }
+
+    // what `==` compares, read exactly off the text: the sign unless the value
+    // is zero, the integer digits without leading zeros, the fraction digits
+    // without trailing zeros, and the exponent
+    fn form(&self) -> (bool, String, Option<String>, Option<i64>) {
+        let text = self.text();
+        let (significand, exponent) = match text.find(['e', 'E']) {
+            Some(i) => (&text[..i], Some(saturating_exponent(&text[i + 1..]))),
+            None => (&text[..], None),
+        };
+        let unsigned = significand.trim_start_matches(['-', '+']);
+        let (int, frac) = match unsigned.split_once('.') {
+            Some((int, frac)) => (int, Some(frac.trim_end_matches('0'))),
+            // text from parts drops a zero fraction, but not its presence
+            None => (unsigned, self.frac.map(|_| "")),
+        };
+        let int = int.trim_start_matches('0');
+        let zero = int.is_empty() && frac.is_none_or(str::is_empty);
+        let negative = significand.starts_with('-') && !zero;
+        (
+            negative,
+            int.to_string(),
+            frac.map(str::to_string),
+            exponent,
+        )
+    }
+
+    // the number as decimal text, from the source when there is one
+    fn text(&self) -> Cow<'_, str> {
+        match &self.raw {
+            Some(raw) => Cow::Borrowed(raw),
+            None => Cow::Owned(self.text_from_parts()),
+        }
+    }
+
+    /// Returns `(coefficient, scale)` such that the value is exactly
+    /// `coefficient * 10^-scale`, or `None` if the coefficient does not fit an
+    /// `i128`. The scale is never negative.
+    pub fn to_scaled_integer(&self) -> Option<(i128, i32)> {
+        if !self.is_finite() {
+            return None;
+        }
+        scaled_integer(&self.text())
+    }
+
+    /// Whether the number is written minimally: no trailing zeros in the
+    /// fraction and no leading zeros in the exponent, so `1.50`, `1.0` and
+    /// `1e01` are not canonical. Numbers built with `Number::new` are judged
+    /// by their parts.
+    pub fn is_canonical(&self) -> bool {
+        let raw = match &self.raw {
+            Some(raw) => raw,
+            None => return self.frac != Some(0.0),
+        };
+        let (mantissa, exponent) = match raw.find(['e', 'E']) {
+            Some(i) => (&raw[..i], Some(raw[i + 1..].trim_start_matches(['+', '-']))),
+            None => (raw.as_str(), None),
+        };
+
+        if let Some((_, frac)) = mantissa.split_once('.') {
+            if frac.ends_with('0') {
+                return false;
+            }
+        }
+        !matches!(exponent, Some(ex) if ex.starts_with('0'))
+    }
+
+    // best-effort decimal text for numbers built without source text
+    fn text_from_parts(&self) -> String {
+        let mut s = self.mantissa_from_parts();
+        if let Some(ex) = self.exponent {
+            s.push_str(&format!("e{}", ex));
+        }
+        s
+    }
+
+    // the integer and fraction digits side by side; adding them as floats
+    // would round away digits of large integers
+    fn mantissa_from_parts(&self) -> String {
+        let Some(fr) = self.frac else {
+            return self.int.to_string();
+        };
+        match fr.to_string().strip_prefix('0') {
+            Some(digits) => format!("{}{}", self.int, digits),
+            // a fraction that rounded up to 1 carries into the integer part
+            None => (self.int as i64 + if self.int < 0 { -1 } else { 1 }).to_string(),
+        }
+    }
+}
+
+// compares the exact text rather than the lossy parts, but not `raw` itself,
+// so "1.0" and "1.00" are equal while two long integers that round to the
+// same parts are not
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.non_finite, other.non_finite) {
+            (None, None) => self.form() == other.form(),
+            // by bits, so that a NaN literal equals itself
+            (a, b) => a.map(f64::to_bits) == b.map(f64::to_bits),
+        }
+    }
+}
+
+/// Orders by exact decimal value. Numbers of equal value but different form,
+/// such as `1` and `1.0`, are not `==`, so they are ordered by form to agree
+/// with it: a plain integer before one with a fraction. `NaN` equals itself
+/// and is unordered against everything else.
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self == other {
+            return Some(Ordering::Equal);
+        }
+        match (self.non_finite, other.non_finite) {
+            (None, None) => {}
+            // any finite number lies strictly between the infinities
+            (a, b) => return a.unwrap_or(0.0).partial_cmp(&b.unwrap_or(0.0)),
+        }
+        let (a, b) = (self.form(), other.form());
+        let by_form = (a.2.is_some(), a.3.is_some(), &a).cmp(&(b.2.is_some(), b.3.is_some(), &b));
+        Some(compare_scientific(self.scientific(), other.scientific()).then(by_form))
+    }
+}
+
+/// Equality compares text, so it is reflexive even for numbers built from a
+/// NaN fraction. The `NaN` literal is compared by bits and does equal itself.
+impl Eq for Number {}
+
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.form().hash(state);
+        self.non_finite.map(f64::to_bits).hash(state);
+    }
+}
+
+// orders the `(negative, digits, exponent)` triples of `Number::scientific` by value
+fn compare_scientific(a: (bool, String, i64), b: (bool, String, i64)) -> Ordering {
+    let sign = |(negative, digits, _): &(bool, String, i64)| match (digits.is_empty(), negative) {
+        (true, _) => 0,
+        (false, true) => -1,
+        (false, false) => 1,
+    };
+    sign(&a).cmp(&sign(&b)).then_with(|| {
+        let magnitude = (a.2, &a.1).cmp(&(b.2, &b.1));
+        if a.0 {
+            magnitude.reverse()
+        } else {
+            magnitude
+        }
+    })
+}
+
+// `Number::scientific` for number text
+fn scientific(text: &str) -> (bool, String, i64) {
+    let (significand, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], &text[i + 1..]),
+        None => (text, "0"),
+    };
+    let exponent = saturating_exponent(exponent);
+    let negative = significand.starts_with('-');
+    let (int, frac) = significand
+        .trim_start_matches(['-', '+'])
+        .split_once('.')
+        .unwrap_or((significand.trim_start_matches(['-', '+']), ""));
+    let digits = int.to_string() + frac;
+    let Some(first) = digits.find(|c| c != '0') else {
+        return (false, String::new(), 0);
+    };
+    let shift = int.len() as i64 - 1 - first as i64;
+    let significant = digits[first..].trim_end_matches('0').to_string();
+    (negative, significant, exponent.saturating_add(shift))
+}
+
+// parses exponent text, saturating; exponents too long for an `i64` only
+// occur in absurd input
+fn saturating_exponent(text: &str) -> i64 {
+    text.parse::<i64>().unwrap_or(if text.starts_with('-') {
+        i64::MIN
+    } else {
+        i64::MAX
+    })
+}
+
+/// Decomposes number text with the same rules the tokenizer uses.
+impl FromStr for Number {
+    type Err = TokenizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let options = ParserOptions {
+            allow_leading_plus: true,
+            ..Default::default()
+        };
+        let mut t = Tokenizer::with_options(s.to_string(), options);
+        let number = t.consume_number()?;
+        match (number, t.peek()) {
+            (JToken::Number(n), None) => Ok(n),
+            _ => Err(TokenizeError::InvalidNumber(t.pos)),
+        }
+    }
+}
+
+// checks the RFC 8259 number grammar, which is stricter than what the tokenizer accepts
+fn is_json_number(text: &str) -> bool {
+    let b = text.as_bytes();
+    let mut i = 0;
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while *i < b.len() && b[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        *i - start
+    };
+
+    if b.first() == Some(&b'-') {
+        i += 1;
+    }
+    match b.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => {
+            digits(&mut i);
+        }
+        _ => return false,
+    }
+    if b.get(i) == Some(&b'.') {
+        i += 1;
+        if digits(&mut i) == 0 {
+            return false;
+        }
+    }
+    if matches!(b.get(i), Some(b'e' | b'E')) {
+        i += 1;
+        if matches!(b.get(i), Some(b'+' | b'-')) {
+            i += 1;
+        }
+        if digits(&mut i) == 0 {
+            return false;
+        }
+    }
+    i == b.len()
+}
+
+fn scaled_integer(text: &str) -> Option<(i128, i32)> {
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], text[i + 1..].parse::<i32>().ok()?),
+        None => (text, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let negative = int.starts_with('-');
+    let digits = int.trim_start_matches(['-', '+']).to_string() + frac;
+
+    let mut coefficient = 0i128;
+    for d in digits.bytes() {
+        coefficient = coefficient
+            .checked_mul(10)?
+            .checked_add((d - b'0') as i128)?;
+    }
+    let mut scale = (frac.len() as i32).checked_sub(exponent)?;
+    while scale < 0 {
+        coefficient = coefficient.checked_mul(10)?;
+        scale += 1;
+    }
+
+    if negative {
+        coefficient = -coefficient;
+    }
+    Some((coefficient, scale))
+}
+
+/// The token as it would be written in JSON, so `}`, `true`, `1.5` or
+/// `"a\nb"` with its escapes.
+impl Display for JToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JToken::LeftBrace => f.write_str("{"),
+            JToken::RightBrace => f.write_str("}"),
+            JToken::LeftBracket => f.write_str("["),
+            JToken::RightBracket => f.write_str("]"),
+            JToken::Collon => f.write_str(":"),
+            JToken::Comma => f.write_str(","),
+            JToken::Null => f.write_str("null"),
+            JToken::Bool(b) => write!(f, "{}", b),
+            JToken::Number(n) => write!(f, "{}", n),
+            JToken::String(s) => write_string(f, s),
+            JToken::RawNumber(s) | JToken::Ident(s) => f.write_str(s),
         }
     }
 }
@@ -36,238 +523,742 @@ impl Number {
 impl Display for Number {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Number {
-            int,
-            frac,
             exponent,
+            raw,
+            non_finite,
+            ..
         } = self;
 
-        match (frac, exponent) {
-            (Some(fr), Some(ex)) => {
-                let v = if *int >= 0 {
-                    (*int as f32) + fr
-                } else {
-                    (*int as f32) - fr
-                };
-                write!(f, "{}E{:+}", v, ex)
+        match non_finite {
+            Some(v) if v.is_nan() => return write!(f, "NaN"),
+            Some(v) if *v > 0.0 => return write!(f, "Infinity"),
+            Some(_) => return write!(f, "-Infinity"),
+            None => {}
+        }
+
+        // lenient forms like "+1" or ".5" are rebuilt from the parts instead
+        if let Some(raw) = raw.as_deref().filter(|raw| is_json_number(raw)) {
+            return f.write_str(raw);
+        }
+
+        f.write_str(&self.mantissa_from_parts())?;
+        match exponent {
+            Some(ex) => write!(f, "E{:+}", ex),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenizeError {
+    UnexpectedChar(char, usize),
+    UnterminatedString(usize),
+    InvalidNumber(usize),
+    InvalidKeyword(String, usize),
+    TotalStringBytesExceeded(usize),
+    /// A raw character below U+0020 inside a string; it must be escaped.
+    UnescapedControlChar(char, usize),
+    /// An unknown escape, malformed `\u` digits, or a lone surrogate.
+    InvalidEscape(usize),
+    /// The input is longer than `max_input_len`; carries that limit.
+    InputTooLong(usize),
+    /// The string starting here is longer than `max_string_len`.
+    StringTooLong(usize),
+    /// Bytes read by `Parser::from_reader` that are not valid UTF-8, starting
+    /// here. Also raised for a multi-byte sequence cut off by the end of input.
+    InvalidUtf8(usize),
+    /// A `/*` comment starting here has no closing `*/`.
+    UnterminatedComment(usize),
+}
+
+impl TokenizeError {
+    /// Byte offset the error was found at. For `InputTooLong` this is the
+    /// limit, where the input stopped being acceptable.
+    pub fn pos(&self) -> usize {
+        match self {
+            TokenizeError::UnexpectedChar(_, pos)
+            | TokenizeError::UnterminatedString(pos)
+            | TokenizeError::InvalidNumber(pos)
+            | TokenizeError::InvalidKeyword(_, pos)
+            | TokenizeError::TotalStringBytesExceeded(pos)
+            | TokenizeError::UnescapedControlChar(_, pos)
+            | TokenizeError::InvalidEscape(pos)
+            | TokenizeError::InputTooLong(pos)
+            | TokenizeError::StringTooLong(pos)
+            | TokenizeError::InvalidUtf8(pos)
+            | TokenizeError::UnterminatedComment(pos) => *pos,
+        }
+    }
+}
+
+impl Display for TokenizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenizeError::UnexpectedChar(c, pos) => {
+                write!(f, "unexpected character {:?} at {}", c, pos)
             }
-            (Some(fr), None) => {
-                let v = if *int >= 0 {
-                    (*int as f32) + fr
-                } else {
-                    (*int as f32) - fr
-                };
-                write!(f, "{}", v)
+            TokenizeError::UnterminatedString(pos) => {
+                write!(f, "unterminated string starting at {}", pos)
+            }
+            TokenizeError::InvalidNumber(pos) => write!(f, "invalid number at {}", pos),
+            TokenizeError::InvalidKeyword(s, pos) => {
+                write!(f, "invalid keyword {:?} at {}", s, pos)
+            }
+            TokenizeError::TotalStringBytesExceeded(pos) => {
+                write!(f, "total string length limit exceeded at {}", pos)
+            }
+            TokenizeError::UnescapedControlChar(c, pos) => {
+                write!(
+                    f,
+                    "unescaped control character {:?} in string at {}",
+                    c, pos
+                )
             }
-            (None, Some(ex)) => {
-                write!(f, "{}E{:+}", int, ex)
+            TokenizeError::InvalidEscape(pos) => write!(f, "invalid escape sequence at {}", pos),
+            TokenizeError::InputTooLong(max) => write!(f, "input is longer than {} bytes", max),
+            TokenizeError::StringTooLong(pos) => {
+                write!(f, "string starting at {} exceeds the length limit", pos)
             }
-            (None, None) => {
-                write!(f, "{}", int)
+            TokenizeError::InvalidUtf8(pos) => write!(f, "invalid UTF-8 at {}", pos),
+            TokenizeError::UnterminatedComment(pos) => {
+                write!(f, "unterminated comment starting at {}", pos)
             }
         }
     }
 }
 
-pub struct Tokenizer {
-    input: Peekable<IntoIter<char>>,
+impl std::error::Error for TokenizeError {}
+
+/// A token as the parser takes it, with string contents borrowed from the
+/// input where there is nothing to unescape.
+pub(crate) enum Lexeme<'a> {
+    Str(Cow<'a, str>),
+    Token(JToken),
+}
+
+impl Lexeme<'_> {
+    pub(crate) fn kind(&self) -> TokenKind {
+        match self {
+            Lexeme::Str(_) => TokenKind::String,
+            Lexeme::Token(token) => token.kind(),
+        }
+    }
+
+    pub(crate) fn into_token(self) -> JToken {
+        match self {
+            Lexeme::Str(s) => JToken::String(s.into_owned()),
+            Lexeme::Token(token) => token,
+        }
+    }
+}
+
+/// Splits JSON text into tokens, for tools that work below the level of
+/// values, such as highlighters and linters. `tokenize` returns all tokens at
+/// once; otherwise a `Tokenizer` is an iterator of tokens, and `spanned` adds
+/// where each one is in the input. After an error, iteration carries on with
+/// the text that follows.
+pub struct Tokenizer<'a> {
+    input: Cow<'a, str>,
+    // byte offset of the next unread character
+    pos: usize,
+    options: ParserOptions,
+    // bytes of string content read so far, keys included
+    string_bytes: usize,
+    // comments skipped since the parser last took them, when recording
+    comments: Vec<Comment>,
+    // whether a line break was skipped since the last token
+    line_break: bool,
+}
+
+/// A comment as written, delimiters included.
+pub(crate) struct Comment {
+    pub(crate) text: String,
+    /// Nothing but whitespace precedes it on its line.
+    pub(crate) own_line: bool,
 }
 
-impl Tokenizer {
+impl Tokenizer<'static> {
     pub fn new(input: String) -> Self {
-        let cs = input.chars().collect::<Vec<char>>();
+        Self::with_options(input, ParserOptions::default())
+    }
+
+    pub fn with_options(input: String, options: ParserOptions) -> Self {
+        Self::from_cow(Cow::Owned(input), options)
+    }
+
+    /// All tokens of `input`, under the default options, stopping at the
+    /// first error.
+    pub fn tokenize(input: &str) -> Result<Vec<JToken>, TokenizeError> {
+        Tokenizer::borrowed(input, ParserOptions::default()).collect()
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    /// Tokenizes text the caller keeps owning. The tokens this yields are
+    /// owned all the same; the parser is what borrows from it, see
+    /// `Parser::parse_borrowed`.
+    pub fn borrowed(input: &'a str, options: ParserOptions) -> Self {
+        Self::from_cow(Cow::Borrowed(input), options)
+    }
+
+    fn from_cow(input: Cow<'a, str>, options: ParserOptions) -> Self {
         Self {
-            input: cs.into_iter().peekable(),
+            input,
+            pos: 0,
+            options,
+            string_bytes: 0,
+            comments: Vec::new(),
+            line_break: true,
         }
     }
 
-    pub fn consume_string(&mut self) -> JToken {
-        let c = self.input.next();
-        assert_eq!(c, Some('"'));
+    pub fn set_options(&mut self, options: ParserOptions) {
+        self.options = options;
+    }
 
-        let mut s = "".to_string();
-        loop {
-            let c = self.input.next();
-            match c {
-                Some('"') => break,
-                Some(c) => s.push(c),
-                None => panic!("unclosed string."),
+    pub(crate) fn input(&self) -> &str {
+        &self.input
+    }
+
+    // moves the cursor past the first line break at or after `from`, or to
+    // the end of the input, forgetting what was skipped since the last token
+    pub(crate) fn skip_line(&mut self, from: usize) {
+        self.pos = match self.input.get(from..).and_then(|rest| rest.find('\n')) {
+            Some(i) => from + i + 1,
+            None => self.input.len(),
+        };
+        self.comments.clear();
+        self.line_break = true;
+    }
+
+    pub(crate) fn take_comments(&mut self) -> Vec<Comment> {
+        std::mem::take(&mut self.comments)
+    }
+
+    fn allows_comments(&self) -> bool {
+        self.options.allow_comments || self.options.record_comments
+    }
+
+    // everything outside string contents is ASCII, so most of the scanning
+    // looks at single bytes and never has to decode UTF-8
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.pos).copied()
+    }
+
+    // only called after `peek` returned an ASCII byte
+    fn bump(&mut self) {
+        self.pos += 1;
+    }
+
+    fn bump_if(&mut self, f: impl Fn(u8) -> bool) -> bool {
+        match self.peek() {
+            Some(b) if f(b) => {
+                self.bump();
+                true
             }
+            _ => false,
         }
-        JToken::String(s)
     }
 
-    fn consume_int(&mut self) -> i32 {
-        let mut n = "".to_string();
+    // consumes the character under the cursor, which may be multi-byte, and
+    // reports it; only called when there is one
+    fn unexpected_char(&mut self) -> TokenizeError {
+        let start = self.pos;
+        let c = self.input[start..].chars().next().unwrap_or('\0');
+        self.pos += c.len_utf8();
+        TokenizeError::UnexpectedChar(c, start)
+    }
+
+    // returns how many consecutive digits were read
+    fn consume_digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.bump_if(|b| b.is_ascii_digit()) {}
+        self.pos - start
+    }
+
+    // the contents of the string under the cursor, borrowed from a borrowed
+    // input when there are no escapes
+    fn scan_string(&mut self) -> Result<Cow<'a, str>, TokenizeError> {
+        let start = self.pos;
+        let quote = match self.peek() {
+            Some(b'"') => b'"',
+            Some(b'\'') if self.options.allow_single_quotes => b'\'',
+            Some(_) => return Err(self.unexpected_char()),
+            None => return Err(TokenizeError::UnterminatedString(start)),
+        };
+        self.bump();
+
+        let mut s = String::new();
         loop {
-            let c = self.input.peek();
-            match c {
-                Some(&c) if c == '-' || c == '+' => {
-                    if n.is_empty() {
-                        self.input.next();
-                        n.push(c);
-                    } else {
-                        panic!("invalid sign position.");
+            // copy the run up to the closing quote, a backslash or a control
+            // character; none of them occur inside a multi-byte UTF-8 sequence
+            let run = self.input.as_bytes()[self.pos..]
+                .iter()
+                .position(|&b| b == quote || b == b'\\' || b < 0x20);
+            let Some(len) = run else {
+                return Err(TokenizeError::UnterminatedString(start));
+            };
+            self.check_string_len(s.len() + len, start)?;
+            let content = self.pos..self.pos + len;
+            self.count_string_bytes(content.clone())?;
+            self.pos += len;
+
+            match self.peek() {
+                Some(b) if b == quote => {
+                    self.bump();
+                    // a single run has nothing to unescape; `s` is only
+                    // non-empty once an escape was seen
+                    if s.is_empty() {
+                        return Ok(self.slice(content));
                     }
+                    s.push_str(&self.input[content]);
+                    break;
                 }
-                Some(&c) if c.is_numeric() => {
-                    self.input.next();
-                    n.push(c);
+                Some(b'\\') => {
+                    s.push_str(&self.input[content]);
+                    let at = self.pos;
+                    let c = self.consume_escape()?;
+                    self.check_string_len(s.len() + c.len_utf8(), start)?;
+                    self.count_escaped_char(c, at)?;
+                    s.push(c);
                 }
-                _ => break,
+                Some(b) => return Err(TokenizeError::UnescapedControlChar(b as char, self.pos)),
+                None => return Err(TokenizeError::UnterminatedString(start)),
             }
         }
-
-        n.parse::<i32>().unwrap_or(0)
+        Ok(Cow::Owned(s))
     }
 
-    fn consume_frac(&mut self) -> Option<f32> {
-        let c = self.input.peek();
-        match c {
-            Some(&c) if c == '.' => {
-                self.input.next();
+    fn slice(&self, range: Range<usize>) -> Cow<'a, str> {
+        match &self.input {
+            Cow::Borrowed(input) => {
+                let input: &'a str = input;
+                Cow::Borrowed(&input[range])
             }
-            _ => return None,
+            Cow::Owned(input) => Cow::Owned(input[range].to_string()),
         }
+    }
 
-        let mut n = ".".to_string();
-        loop {
-            let c = self.input.peek();
-            match c {
-                Some(&c) if c.is_numeric() => {
-                    self.input.next();
-                    n.push(c);
+    // reads the escape sequence starting at the backslash under the cursor
+    fn consume_escape(&mut self) -> Result<char, TokenizeError> {
+        let start = self.pos;
+        self.bump();
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\'') if self.options.allow_single_quotes => '\'',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{08}',
+            Some(b'f') => '\u{0c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.bump();
+                return self.consume_unicode_escape(start);
+            }
+            _ => return Err(TokenizeError::InvalidEscape(start)),
+        };
+        self.bump();
+        Ok(c)
+    }
+
+    // the hex digits of a `\u` escape, joining a UTF-16 surrogate pair written
+    // as two escapes; a lone surrogate is not a character and is rejected
+    fn consume_unicode_escape(&mut self, start: usize) -> Result<char, TokenizeError> {
+        let unit = self.consume_hex4(start)?;
+        let code = match unit {
+            0xD800..=0xDBFF => {
+                if !self.input[self.pos..].starts_with("\\u") {
+                    return Err(TokenizeError::InvalidEscape(start));
                 }
-                _ => break,
+                self.pos += 2;
+                let low = self.consume_hex4(start)?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(TokenizeError::InvalidEscape(start));
+                }
+                0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00)
             }
+            unit => unit,
+        };
+        char::from_u32(code).ok_or(TokenizeError::InvalidEscape(start))
+    }
+
+    fn consume_hex4(&mut self, start: usize) -> Result<u32, TokenizeError> {
+        let code = self
+            .input
+            .get(self.pos..self.pos + 4)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or(TokenizeError::InvalidEscape(start))?;
+        self.pos += 4;
+        Ok(code)
+    }
+
+    fn check_string_len(&self, len: usize, start: usize) -> Result<(), TokenizeError> {
+        match self.options.max_string_len {
+            Some(max) if len > max => Err(TokenizeError::StringTooLong(start)),
+            _ => Ok(()),
+        }
+    }
+
+    fn string_budget(&self) -> usize {
+        match self.options.max_total_string_bytes {
+            Some(max) => max.saturating_sub(self.string_bytes),
+            None => usize::MAX,
+        }
+    }
+
+    fn count_string_bytes(&mut self, content: Range<usize>) -> Result<(), TokenizeError> {
+        let budget = self.string_budget();
+        if content.len() > budget {
+            // offset of the character that went over the limit
+            let over = self.input[content.clone()]
+                .char_indices()
+                .find(|(i, c)| i + c.len_utf8() > budget)
+                .map_or(content.start, |(i, _)| content.start + i);
+            return Err(TokenizeError::TotalStringBytesExceeded(over));
+        }
+        self.string_bytes += content.len();
+        Ok(())
+    }
+
+    // an escape counts as the bytes of the character it stands for
+    fn count_escaped_char(&mut self, c: char, at: usize) -> Result<(), TokenizeError> {
+        if c.len_utf8() > self.string_budget() {
+            return Err(TokenizeError::TotalStringBytesExceeded(at));
+        }
+        self.string_bytes += c.len_utf8();
+        Ok(())
+    }
+
+    // whether a hex literal starts at the cursor, after an optional sign
+    fn at_hex_prefix(&self) -> bool {
+        let rest = &self.input[self.pos..];
+        let rest = rest.strip_prefix(['-', '+']).unwrap_or(rest);
+        self.options.allow_hex_numbers && (rest.starts_with("0x") || rest.starts_with("0X"))
+    }
+
+    fn consume_int(&mut self) -> Result<(i32, usize), TokenizeError> {
+        let start = self.pos;
+        if self.at_hex_prefix() {
+            return self.consume_hex_int();
+        }
+        self.bump_if(|b| b == b'-' || b == b'+');
+
+        let digits = self.consume_digits();
+        if digits == 0 {
+            return Ok((0, 0));
+        }
+
+        self.input[start..self.pos]
+            .parse::<i32>()
+            .map(|int| (int, digits))
+            .map_err(|_| TokenizeError::InvalidNumber(start))
+    }
+
+    fn consume_hex_int(&mut self) -> Result<(i32, usize), TokenizeError> {
+        let start = self.pos;
+        let negative = self.peek() == Some(b'-');
+        self.bump_if(|b| b == b'-' || b == b'+');
+        self.pos += 2;
+
+        let digits_start = self.pos;
+        while self.bump_if(|b| b.is_ascii_hexdigit()) {}
+        let digits = &self.input[digits_start..self.pos];
+        let magnitude =
+            i64::from_str_radix(digits, 16).map_err(|_| TokenizeError::InvalidNumber(start))?;
+        let int = if negative { -magnitude } else { magnitude };
+
+        i32::try_from(int)
+            .map(|int| (int, digits.len()))
+            .map_err(|_| TokenizeError::InvalidNumber(start))
+    }
+
+    fn consume_frac(&mut self) -> (Option<f32>, usize) {
+        let start = self.pos;
+        if !self.bump_if(|b| b == b'.') {
+            return (None, 0);
+        }
+
+        let digits = self.consume_digits();
+
+        (self.input[start..self.pos].parse::<f32>().ok(), digits)
+    }
+
+    // `number_start` is where the error points when the exponent is missing
+    // its digits. One that does not fit an `i32` is clamped; `raw` keeps it.
+    fn consume_exponent(&mut self, number_start: usize) -> Result<Option<i32>, TokenizeError> {
+        if !self.bump_if(|b| b == b'e' || b == b'E') {
+            return Ok(None);
+        }
+
+        let start = self.pos;
+        self.bump_if(|b| b == b'-' || b == b'+');
+        if self.consume_digits() == 0 {
+            return Err(TokenizeError::InvalidNumber(number_start));
+        }
+
+        let exponent = saturating_exponent(&self.input[start..self.pos]);
+        Ok(Some(exponent.clamp(i32::MIN as i64, i32::MAX as i64) as i32))
+    }
+
+    fn consume_number(&mut self) -> Result<JToken, TokenizeError> {
+        let start = self.pos;
+        // the whole number is consumed either way, so tokenizing resumes after it
+        let token = self.consume_number_token();
+        if self.input[start..].starts_with('+') && !self.options.allow_leading_plus {
+            return Err(TokenizeError::InvalidNumber(start));
+        }
+        token
+    }
+
+    fn consume_number_token(&mut self) -> Result<JToken, TokenizeError> {
+        // hex text is not JSON, so it is decomposed even in raw mode
+        if self.options.raw_numbers && !self.at_hex_prefix() {
+            return self.consume_raw_number();
+        }
+        let start = self.pos;
+        let hex = self.at_hex_prefix();
+        let number = self.consume_number_parts();
+        // hex text would not be valid JSON to write back out
+        let raw = (!hex).then(|| self.input[start..self.pos].to_string());
+
+        number.map(|number| JToken::Number(Number { raw, ..number }))
+    }
+
+    // follows the shape of `consume_number_parts` without parsing any values
+    fn consume_raw_number(&mut self) -> Result<JToken, TokenizeError> {
+        let start = self.pos;
+        let negative = self.peek() == Some(b'-');
+        self.bump_if(|b| b == b'-' || b == b'+');
+        let mut digits = self.consume_digits();
+        if self.bump_if(|b| b == b'.') {
+            digits += self.consume_digits();
+        }
+        if digits == 0 && self.options.allow_non_finite && self.peek() == Some(b'I') {
+            let sign_end = self.pos;
+            self.consume_word();
+            return match &self.input[sign_end..self.pos] {
+                "Infinity" if negative => {
+                    Ok(JToken::Number(Number::new_non_finite(f64::NEG_INFINITY)))
+                }
+                "Infinity" => Ok(JToken::Number(Number::new_non_finite(f64::INFINITY))),
+                _ => Err(TokenizeError::InvalidNumber(start)),
+            };
+        }
+        if digits == 0 {
+            return Err(TokenizeError::InvalidNumber(start));
+        }
+
+        if self.bump_if(|b| b == b'e' || b == b'E') {
+            self.bump_if(|b| b == b'-' || b == b'+');
+            self.consume_digits();
         }
+        Ok(JToken::RawNumber(self.input[start..self.pos].to_string()))
+    }
 
-        n.parse::<f32>().ok()
+    fn consume_number_parts(&mut self) -> Result<Number, TokenizeError> {
+        let start = self.pos;
+        let hex = self.at_hex_prefix();
+        let (int, int_digits) = match self.consume_int() {
+            Err(_) if !hex => return self.consume_big_number(start),
+            result => result?,
+        };
+        if hex {
+            return Ok(Number::new(int, None, None));
+        }
+        if int_digits == 0 && self.options.allow_non_finite && self.peek() == Some(b'I') {
+            let negative = &self.input[start..self.pos] == "-";
+            let word_start = self.pos;
+            self.consume_word();
+            return match &self.input[word_start..self.pos] {
+                "Infinity" if negative => Ok(Number::new_non_finite(f64::NEG_INFINITY)),
+                "Infinity" => Ok(Number::new_non_finite(f64::INFINITY)),
+                _ => Err(TokenizeError::InvalidNumber(start)),
+            };
+        }
+        let (frac, frac_digits) = self.consume_frac();
+        if int_digits + frac_digits == 0 {
+            return Err(TokenizeError::InvalidNumber(start));
+        }
+        let exponent = self.consume_exponent(start)?;
+
+        Ok(Number::new(int, frac, exponent))
     }
 
-    fn consume_exponent(&mut self) -> Option<i32> {
-        let c = self.input.peek();
-        let mut n = "".to_string();
+    // finishes a number whose integer part overflowed `consume_int`, taking
+    // its parts from the digits in scientific notation, as in `3e9`;
+    // `consume_number` keeps the exact text
+    fn consume_big_number(&mut self, start: usize) -> Result<Number, TokenizeError> {
+        self.consume_frac();
+        self.consume_exponent(start)?;
+        let (negative, digits, exponent) = scientific(&self.input[start..self.pos]);
+        // the integer part overflowed, so it has a nonzero digit
+        let first = (digits.as_bytes()[0] - b'0') as i32;
+        let frac = (digits.len() > 1)
+            .then(|| format!("0.{}", &digits[1..]).parse::<f32>().ok())
+            .flatten();
+        let exponent = exponent.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        Ok(Number::new(
+            if negative { -first } else { first },
+            frac,
+            Some(exponent),
+        ))
+    }
+
+    // skips a run of ASCII letters; the caller slices it out of `input`
+    fn consume_word(&mut self) {
+        while self.bump_if(|b| b.is_ascii_alphabetic()) {}
+    }
+
+    fn consume_keyword(&mut self) -> Result<JToken, TokenizeError> {
+        let start = self.pos;
+        self.consume_word();
+        if self.options.allow_unquoted_keys {
+            while self.bump_if(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'$') {}
+        }
 
-        match c {
-            Some(&c) if c == 'e' || c == 'E' => {
-                self.input.next();
+        match &self.input[start..self.pos] {
+            "null" => Ok(JToken::Null),
+            "true" => Ok(JToken::Bool(true)),
+            "false" => Ok(JToken::Bool(false)),
+            "NaN" if self.options.allow_non_finite => {
+                Ok(JToken::Number(Number::new_non_finite(f64::NAN)))
+            }
+            "Infinity" if self.options.allow_non_finite => {
+                Ok(JToken::Number(Number::new_non_finite(f64::INFINITY)))
             }
-            _ => return None,
+            s if self.options.allow_unquoted_keys && !s.is_empty() => {
+                Ok(JToken::Ident(s.to_string()))
+            }
+            s => Err(TokenizeError::InvalidKeyword(s.to_string(), start)),
         }
+    }
+}
 
+impl<'a> Tokenizer<'a> {
+    // comments count as whitespace when allowed. An unterminated one is left
+    // in place for `next_lexeme` to report.
+    fn skip_whitespace(&mut self) {
         loop {
-            let c = self.input.peek();
-            match c {
-                Some(&c) if c == '-' || c == '+' => {
-                    if n.is_empty() {
-                        self.input.next();
-                        n.push(c);
+            match self.peek() {
+                Some(b'\n') => {
+                    self.line_break = true;
+                    self.bump();
+                }
+                Some(b' ' | b'\t' | b'\r') => self.bump(),
+                Some(b'/') if self.allows_comments() => {
+                    let start = self.pos;
+                    let rest = &self.input[start..];
+                    let len = if rest.starts_with("//") {
+                        rest.find('\n').unwrap_or(rest.len())
+                    } else if let Some(body) = rest.strip_prefix("/*") {
+                        match body.find("*/") {
+                            Some(i) => i + 4,
+                            None => return,
+                        }
                     } else {
-                        panic!("invalid sign position.");
+                        return;
+                    };
+                    self.pos += len;
+                    if self.options.record_comments {
+                        let text = self.input[start..self.pos].trim_end().to_string();
+                        let own_line = self.line_break;
+                        self.comments.push(Comment { text, own_line });
                     }
                 }
-                Some(&c) if c.is_numeric() => {
-                    self.input.next();
-                    n.push(c);
-                }
-                _ => break,
+                _ => return,
             }
         }
-
-        n.parse::<i32>().ok()
     }
 
-    pub fn consume_number(&mut self) -> JToken {
-        let int = self.consume_int();
-        let frac = self.consume_frac();
-        let exponent = self.consume_exponent();
-
-        JToken::Number(Number {
-            int,
-            frac,
-            exponent,
-        })
+    /// Like `next`, but also reports the byte range the token covers.
+    pub fn next_spanned(&mut self) -> Option<Result<(JToken, Span), TokenizeError>> {
+        let next = self.next_lexeme()?;
+        Some(next.map(|(lexeme, span)| (lexeme.into_token(), span)))
     }
 
-    pub fn consume_keyword(&mut self) -> JToken {
-        let mut s = "".to_string();
-        loop {
-            let c = self.input.peek();
-            match c {
-                Some(&c) if c.is_ascii_lowercase() => {
-                    self.input.next();
-                    s.push(c);
-                }
-                _ => break,
+    pub(crate) fn next_lexeme(&mut self) -> Option<Result<(Lexeme<'a>, Span), TokenizeError>> {
+        if let Some(max) = self.options.max_input_len {
+            if self.input.len() > max && self.pos < self.input.len() {
+                // nothing more is read from an oversized input
+                self.pos = self.input.len();
+                return Some(Err(TokenizeError::InputTooLong(max)));
             }
         }
+        self.skip_whitespace();
+        let start = self.pos;
+        let b = self.peek()?;
+        self.line_break = false;
 
-        match s.as_str() {
-            "null" => JToken::Null,
-            "true" => JToken::Bool(true),
-            "false" => JToken::Bool(false),
-            _ => panic!("invalid keyword {:?}", s),
-        }
+        let token = match b {
+            b'{' => {
+                self.bump();
+                Ok(JToken::LeftBrace)
+            }
+            b'}' => {
+                self.bump();
+                Ok(JToken::RightBrace)
+            }
+            b'[' => {
+                self.bump();
+                Ok(JToken::LeftBracket)
+            }
+            b']' => {
+                self.bump();
+                Ok(JToken::RightBracket)
+            }
+            b':' => {
+                self.bump();
+                Ok(JToken::Collon)
+            }
+            b',' => {
+                self.bump();
+                Ok(JToken::Comma)
+            }
+            b'"' => return Some(self.lexeme_string(start)),
+            b'\'' if self.options.allow_single_quotes => return Some(self.lexeme_string(start)),
+            b'0'..=b'9' | b'-' | b'+' | b'.' => self.consume_number(),
+            b'a'..=b'z' | b'A'..=b'Z' => self.consume_keyword(),
+            b'_' | b'$' if self.options.allow_unquoted_keys => self.consume_keyword(),
+            b'/' if self.allows_comments() && self.input[start..].starts_with("/*") => {
+                self.pos = self.input.len();
+                Err(TokenizeError::UnterminatedComment(start))
+            }
+            _ => Err(self.unexpected_char()),
+        };
+        Some(token.map(|t| (Lexeme::Token(t), Span::new(start, self.pos))))
+    }
+
+    fn lexeme_string(&mut self, start: usize) -> Result<(Lexeme<'a>, Span), TokenizeError> {
+        let s = self.scan_string()?;
+        Ok((Lexeme::Str(s), Span::new(start, self.pos)))
+    }
+
+    /// Turns the tokenizer into an iterator of tokens paired with their spans.
+    pub fn spanned(self) -> Spanned<'a> {
+        Spanned { t: self }
     }
 }
 
-impl Iterator for Tokenizer {
-    type Item = JToken;
+impl Iterator for Tokenizer<'_> {
+    type Item = Result<JToken, TokenizeError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let c = self.input.peek();
+        self.next_spanned().map(|r| r.map(|(token, _)| token))
+    }
+}
 
-            if c.is_none() {
-                return None;
-            }
+pub struct Spanned<'a> {
+    t: Tokenizer<'a>,
+}
 
-            match c.unwrap() {
-                ' ' | '\t' | '\n' => {
-                    self.input.next();
-                    continue;
-                }
-                '{' => {
-                    self.input.next();
-                    return Some(JToken::LeftBrace);
-                }
-                '}' => {
-                    self.input.next();
-                    return Some(JToken::RightBrace);
-                }
-                '[' => {
-                    self.input.next();
-                    return Some(JToken::LeftBracket);
-                }
-                ']' => {
-                    self.input.next();
-                    return Some(JToken::RightBracket);
-                }
-                ':' => {
-                    self.input.next();
-                    return Some(JToken::Collon);
-                }
-                ',' => {
-                    self.input.next();
-                    return Some(JToken::Comma);
-                }
-                '"' => {
-                    let token = self.consume_string();
-                    return Some(token);
-                }
-                '0'..='9' | '-' | '+' | '.' => {
-                    let n = self.consume_number();
-                    return Some(n);
-                }
-                'a'..='z' | 'A'..='Z' => {
-                    let token = self.consume_keyword();
-                    return Some(token);
-                }
-                c => {
-                    panic!("cannot parse input: {:?}", c);
-                }
-            };
-        }
+impl Iterator for Spanned<'_> {
+    type Item = Result<(JToken, Span), TokenizeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.t.next_spanned()
     }
 }
 
@@ -275,12 +1266,36 @@ impl Iterator for Tokenizer {
 mod tests_display {
     use super::*;
 
+    #[test]
+    fn test_display_token() {
+        let cases = [
+            (JToken::RightBrace, "}"),
+            (JToken::LeftBracket, "["),
+            (JToken::Collon, ":"),
+            (JToken::Comma, ","),
+            (JToken::Null, "null"),
+            (JToken::Bool(false), "false"),
+            (JToken::Number("-1.50".parse().unwrap()), "-1.50"),
+            (
+                JToken::String("say \"hi\"\n".to_string()),
+                "\"say \\\"hi\\\"\\n\"",
+            ),
+            (JToken::RawNumber("1e5".to_string()), "1e5"),
+            (JToken::Ident("foo".to_string()), "foo"),
+        ];
+        for (token, expected) in cases {
+            assert_eq!(token.to_string(), expected);
+        }
+    }
+
     #[test]
     fn test_display_int() {
         let n = Number {
             int: 123,
             frac: None,
             exponent: None,
+            raw: None,
+            non_finite: None,
         };
         let expected = "123";
         assert_eq!(format!("{}", n), expected);
@@ -292,6 +1307,8 @@ mod tests_display {
             int: -123,
             frac: Some(0.456),
             exponent: None,
+            raw: None,
+            non_finite: None,
         };
         let expected = "-123.456";
         assert_eq!(format!("{}", n), expected);
@@ -303,17 +1320,30 @@ mod tests_display {
             int: -123,
             frac: Some(0.456),
             exponent: Some(2),
+            raw: None,
+            non_finite: None,
         };
         let expected = "-123.456E+2";
         assert_eq!(format!("{}", n), expected);
     }
 
+    #[test]
+    fn test_display_large_int_frac() {
+        let n = Number::new(123_456_789, Some(0.5), None);
+        assert_eq!(n.to_string(), "123456789.5");
+
+        let n = Number::new(-2_000_000_001, Some(0.25), Some(-4));
+        assert_eq!(n.to_string(), "-2000000001.25E-4");
+    }
+
     #[test]
     fn test_misc() {
         let n = Number {
             int: 0,
             frac: Some(0.2),
             exponent: Some(-3),
+            raw: None,
+            non_finite: None,
         };
         let expected = "0.2E-3";
         assert_eq!(format!("{}", n), expected);
@@ -323,6 +1353,7 @@ mod tests_display {
 #[cfg(test)]
 mod tests_tokenizer {
     use super::*;
+    use crate::parser::{JValue, Parser};
 
     #[test]
     fn test_brace() {
@@ -330,7 +1361,7 @@ mod tests_tokenizer {
         let mut t = Tokenizer::new(json);
         let expected = [JToken::LeftBrace, JToken::RightBrace];
         for e in expected {
-            assert_eq!(Some(e.clone()), t.next());
+            assert_eq!(Some(Ok(e.clone())), t.next());
         }
         assert!(t.next().is_none());
     }
@@ -347,7 +1378,7 @@ mod tests_tokenizer {
             JToken::RightBrace,
         ];
         for e in expected {
-            assert_eq!(Some(e.clone()), t.next());
+            assert_eq!(Some(Ok(e.clone())), t.next());
         }
         assert!(t.next().is_none());
     }
@@ -368,14 +1399,89 @@ mod tests_tokenizer {
             JToken::RightBrace,
         ];
         for e in expected {
-            assert_eq!(Some(e.clone()), t.next());
+            assert_eq!(Some(Ok(e.clone())), t.next());
         }
         assert!(t.next().is_none());
     }
 
+    #[test]
+    fn test_tokenize() {
+        let tokens = Tokenizer::tokenize("{\"a\": [1, true, null], \"b\": \"x\"}").unwrap();
+
+        assert_eq!(
+            tokens,
+            [
+                JToken::LeftBrace,
+                JToken::String("a".to_string()),
+                JToken::Collon,
+                JToken::LeftBracket,
+                JToken::Number(Number {
+                    int: 1,
+                    frac: None,
+                    exponent: None,
+                    raw: Some("1".to_string()),
+                    non_finite: None,
+                }),
+                JToken::Comma,
+                JToken::Bool(true),
+                JToken::Comma,
+                JToken::Null,
+                JToken::RightBracket,
+                JToken::Comma,
+                JToken::String("b".to_string()),
+                JToken::Collon,
+                JToken::String("x".to_string()),
+                JToken::RightBrace,
+            ]
+        );
+        assert_eq!(Tokenizer::tokenize(""), Ok(vec![]));
+        // tokens do not have to form a valid document
+        assert_eq!(
+            Tokenizer::tokenize("] ,"),
+            Ok(vec![JToken::RightBracket, JToken::Comma])
+        );
+        assert_eq!(
+            Tokenizer::tokenize("[1, @]"),
+            Err(TokenizeError::UnexpectedChar('@', 4))
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let options = ParserOptions {
+            allow_comments: true,
+            ..Default::default()
+        };
+        let input = "// head\n[1, /* two */ 2 // tail\n/**/]";
+        let tokens = Tokenizer::with_options(input.to_string(), options.clone())
+            .map(|t| t.map(|t| t.kind()))
+            .collect::<Result<Vec<_>, _>>();
+        assert_eq!(
+            tokens,
+            Ok(vec![
+                TokenKind::LeftBracket,
+                TokenKind::Number,
+                TokenKind::Comma,
+                TokenKind::Number,
+                TokenKind::RightBracket,
+            ])
+        );
+
+        let mut t = Tokenizer::with_options("[1 /* open".to_string(), options.clone());
+        assert_eq!(t.nth(2), Some(Err(TokenizeError::UnterminatedComment(3))));
+        assert_eq!(t.next(), None);
+
+        let mut t = Tokenizer::with_options("/x".to_string(), options);
+        assert_eq!(t.next(), Some(Err(TokenizeError::UnexpectedChar('/', 0))));
+
+        // not without the option
+        let mut t = Tokenizer::new("// c\n[]".to_string());
+        assert_eq!(t.next(), Some(Err(TokenizeError::UnexpectedChar('/', 0))));
+    }
+
     #[test]
     fn test_number() {
-        let json = "{\"key\": [123, 123.456, -1.0, +1.2, .123, 1E-2, 123.456e+3]}".to_string();
+        let json = "{\"key\": [123, 123.456, -1.0, 1.2, .123, 1E-2, 123.456e+3]}".to_string();
         let mut t = Tokenizer::new(json);
         let expected = [
             JToken::LeftBrace,
@@ -386,48 +1492,62 @@ mod tests_tokenizer {
                 int: 123,
                 frac: None,
                 exponent: None,
+                raw: None,
+                non_finite: None,
             }),
             JToken::Comma,
             JToken::Number(Number {
                 int: 123,
                 frac: Some(0.456),
                 exponent: None,
+                raw: None,
+                non_finite: None,
             }),
             JToken::Comma,
             JToken::Number(Number {
                 int: -1,
                 frac: Some(0.0),
                 exponent: None,
+                raw: None,
+                non_finite: None,
             }),
             JToken::Comma,
             JToken::Number(Number {
                 int: 1,
                 frac: Some(0.2),
                 exponent: None,
+                raw: None,
+                non_finite: None,
             }),
             JToken::Comma,
             JToken::Number(Number {
                 int: 0,
                 frac: Some(0.123),
                 exponent: None,
+                raw: None,
+                non_finite: None,
             }),
             JToken::Comma,
             JToken::Number(Number {
                 int: 1,
                 frac: None,
                 exponent: Some(-2),
+                raw: None,
+                non_finite: None,
             }),
             JToken::Comma,
             JToken::Number(Number {
                 int: 123,
                 frac: Some(0.456),
                 exponent: Some(3),
+                raw: None,
+                non_finite: None,
             }),
             JToken::RightBracket,
             JToken::RightBrace,
         ];
         for e in expected {
-            assert_eq!(Some(e.clone()), t.next());
+            assert_eq!(Some(Ok(e.clone())), t.next());
         }
         assert!(t.next().is_none());
     }
@@ -450,7 +1570,7 @@ mod tests_tokenizer {
             JToken::RightBrace,
         ];
         for e in expected {
-            assert_eq!(Some(e.clone()), t.next());
+            assert_eq!(Some(Ok(e.clone())), t.next());
         }
         assert!(t.next().is_none());
     }
@@ -470,6 +1590,8 @@ mod tests_tokenizer {
                 int: 123,
                 frac: Some(0.456),
                 exponent: Some(-2),
+                raw: None,
+                non_finite: None,
             }),
             JToken::Comma,
             JToken::String("bar".to_string()),
@@ -489,8 +1611,663 @@ mod tests_tokenizer {
             JToken::RightBrace,
         ];
         for e in expected {
-            assert_eq!(Some(e.clone()), t.next());
+            assert_eq!(Some(Ok(e.clone())), t.next());
         }
         assert!(t.next().is_none());
     }
+
+    #[test]
+    fn test_number_without_digits() {
+        for json in ["-", "+", ".", "-.", "[-]"] {
+            let t = Tokenizer::new(json.to_string());
+            let errors = t.filter(|r| r.is_err()).collect::<Vec<_>>();
+            assert!(
+                matches!(errors.as_slice(), [Err(TokenizeError::InvalidNumber(_))]),
+                "{:?}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn test_leading_plus() {
+        for json in ["+1", "[+1.5]", "+Infinity"] {
+            let t = Tokenizer::new(json.to_string());
+            let errors = t.filter(|r| r.is_err()).collect::<Vec<_>>();
+            assert_eq!(
+                errors.first(),
+                Some(&Err(TokenizeError::InvalidNumber(json.find('+').unwrap()))),
+                "{:?}",
+                json
+            );
+        }
+
+        // the exponent keeps its sign
+        let mut t = Tokenizer::new("1e+2".to_string());
+        assert_eq!(
+            t.next(),
+            Some(Ok(JToken::Number(Number {
+                int: 1,
+                frac: None,
+                exponent: Some(2),
+                raw: Some("1e+2".to_string()),
+                non_finite: None,
+            })))
+        );
+
+        for raw_numbers in [false, true] {
+            let options = ParserOptions {
+                allow_leading_plus: true,
+                raw_numbers,
+                ..Default::default()
+            };
+            let value = crate::parser::Parser::with_options("[+1, +2.5e+1]".to_string(), options)
+                .parse()
+                .unwrap();
+            // written back out without the sign, as JSON requires
+            assert_eq!(value.to_string(), "[1,2.5E+1]");
+        }
+    }
+
+    #[test]
+    fn test_number_frac_only() {
+        let mut t = Tokenizer::new("-.5".to_string());
+        assert!(matches!(t.next(), Some(Ok(JToken::Number(_)))));
+        assert!(t.next().is_none());
+    }
+
+    #[test]
+    fn test_scaled_integer() {
+        let cases = [
+            ("1.25", Some((125, 2))),
+            ("1e3", Some((1000, 0))),
+            ("-0.5", Some((-5, 1))),
+            ("12.5E-2", Some((125, 3))),
+            ("0.1", Some((1, 1))),
+            ("1e40", None),
+        ];
+        for (json, expected) in cases {
+            let mut t = Tokenizer::new(json.to_string());
+            match t.next() {
+                Some(Ok(JToken::Number(n))) => assert_eq!(n.to_scaled_integer(), expected),
+                token => panic!("unexpected token {:?}", token),
+            }
+        }
+    }
+
+    #[test]
+    fn test_display_raw() {
+        for json in ["3.141592653589793", "0.1", "-0.5", "1E-2", "100e+05"] {
+            let mut t = Tokenizer::new(json.to_string());
+            match t.next() {
+                Some(Ok(JToken::Number(n))) => assert_eq!(n.to_string(), json),
+                token => panic!("unexpected token {:?}", token),
+            }
+        }
+    }
+
+    #[test]
+    fn test_display_non_json_raw() {
+        let options = ParserOptions {
+            allow_leading_plus: true,
+            ..Default::default()
+        };
+        for (json, expected) in [("+1.5", "1.5"), (".5", "0.5"), ("007", "7")] {
+            let mut t = Tokenizer::with_options(json.to_string(), options.clone());
+            match t.next() {
+                Some(Ok(JToken::Number(n))) => assert_eq!(n.to_string(), expected),
+                token => panic!("unexpected token {:?}", token),
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_canonical() {
+        let cases = [
+            ("1.5", true),
+            ("10", true),
+            ("1e10", true),
+            ("0.05", true),
+            ("1.50", false),
+            ("1.0", false),
+            ("1e01", false),
+            ("1E-007", false),
+        ];
+        for (json, expected) in cases {
+            let mut t = Tokenizer::new(json.to_string());
+            match t.next() {
+                Some(Ok(JToken::Number(n))) => assert_eq!(n.is_canonical(), expected, "{}", json),
+                token => panic!("unexpected token {:?}", token),
+            }
+        }
+    }
+
+    #[test]
+    fn test_scaled_integer_without_raw() {
+        let n = Number::new(-3, Some(0.25), Some(1));
+        assert_eq!(n.to_scaled_integer(), Some((-325, 1)));
+    }
+
+    #[test]
+    fn test_non_finite() {
+        let json = "[NaN, Infinity, -Infinity]".to_string();
+        let options = ParserOptions {
+            allow_non_finite: true,
+            ..Default::default()
+        };
+        let t = Tokenizer::with_options(json, options);
+        let numbers = t
+            .filter_map(|r| match r {
+                Ok(JToken::Number(n)) => Some(n),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(numbers.len(), 3);
+        assert!(numbers[0].non_finite.unwrap().is_nan());
+        assert_eq!(numbers[1].non_finite, Some(f64::INFINITY));
+        assert_eq!(numbers[2].non_finite, Some(f64::NEG_INFINITY));
+        assert!(numbers.iter().all(|n| !n.is_finite()));
+        assert_eq!(numbers[0], numbers[0].clone());
+        assert_eq!(
+            numbers.iter().map(|n| n.to_string()).collect::<Vec<_>>(),
+            ["NaN", "Infinity", "-Infinity"]
+        );
+    }
+
+    #[test]
+    fn test_non_finite_strict() {
+        for json in ["NaN", "Infinity", "-Infinity"] {
+            let mut t = Tokenizer::new(json.to_string());
+            assert!(matches!(t.next(), Some(Err(_))), "{}", json);
+        }
+    }
+
+    #[test]
+    fn test_max_total_string_bytes() {
+        let options = ParserOptions {
+            max_total_string_bytes: Some(10),
+            ..Default::default()
+        };
+
+        let mut t =
+            Tokenizer::with_options("[\"abc\", \"def\", \"ghij\"]".to_string(), options.clone());
+        assert!(t.all(|r| r.is_ok()));
+
+        let mut t =
+            Tokenizer::with_options("{\"abc\": \"def\", \"ghi\": \"jkl\"}".to_string(), options);
+        let error = t.find_map(|r| r.err());
+        assert_eq!(error, Some(TokenizeError::TotalStringBytesExceeded(24)));
+    }
+
+    #[test]
+    fn test_raw_numbers() {
+        let json = "[0, -12.50, 3.141592653589793e+00, .5, 1E9]";
+        let options = ParserOptions {
+            raw_numbers: true,
+            ..Default::default()
+        };
+        let t = Tokenizer::with_options(json.to_string(), options);
+        let numbers = t
+            .filter_map(|r| match r {
+                Ok(JToken::RawNumber(s)) => Some(s),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            numbers,
+            ["0", "-12.50", "3.141592653589793e+00", ".5", "1E9"]
+        );
+    }
+
+    #[test]
+    fn test_number_from_str() {
+        assert_eq!(
+            "-12.5".parse::<Number>(),
+            Ok(Number::new(-12, Some(0.5), None))
+        );
+        assert_eq!("1e3".parse::<Number>().unwrap().to_string(), "1e3");
+        assert_eq!("1x".parse::<Number>(), Err(TokenizeError::InvalidNumber(1)));
+    }
+
+    #[test]
+    fn test_spans() {
+        let json = "{\"kéy\": [12.5, true]}".to_string();
+        let t = Tokenizer::new(json.clone()).spanned();
+        let spans = t.map(|r| r.unwrap()).collect::<Vec<_>>();
+
+        assert_eq!(spans[0], (JToken::LeftBrace, Span::new(0, 1)));
+        assert_eq!(
+            spans[1],
+            (JToken::String("kéy".to_string()), Span::new(1, 7))
+        );
+        assert_eq!(spans[2], (JToken::Collon, Span::new(7, 8)));
+        assert_eq!(spans[4].1, Span::new(10, 14));
+        assert_eq!(spans[6], (JToken::Bool(true), Span::new(16, 20)));
+        for (token, span) in &spans[4..] {
+            if let JToken::Number(n) = token {
+                assert_eq!(&json[span.start..span.end], n.to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_unexpected_multibyte_char() {
+        let mut t = Tokenizer::new("[\"日本\", é]".to_string());
+        let tokens = t.by_ref().take(3).collect::<Vec<_>>();
+
+        assert_eq!(tokens[1], Ok(JToken::String("日本".to_string())));
+        assert_eq!(tokens[2], Ok(JToken::Comma));
+        assert_eq!(t.next(), Some(Err(TokenizeError::UnexpectedChar('é', 11))));
+        assert_eq!(t.next(), Some(Ok(JToken::RightBracket)));
+    }
+
+    #[test]
+    fn test_escapes() {
+        let json = r#"["a\"b\\c\/d", "\b\f\n\r\t", "\u0000é😀"]"#.to_string();
+        let strings = Tokenizer::new(json)
+            .filter_map(|t| match t.unwrap() {
+                JToken::String(s) => Some(s),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(strings, ["a\"b\\c/d", "\u{08}\u{0c}\n\r\t", "\u{0}é😀"]);
+    }
+
+    #[test]
+    fn test_control_chars() {
+        let mut raw_tab = Tokenizer::new("\"a\tb\"".to_string());
+        let mut escaped_tab = Tokenizer::new(r#""a\tb""#.to_string());
+
+        assert_eq!(
+            raw_tab.next(),
+            Some(Err(TokenizeError::UnescapedControlChar('\t', 2)))
+        );
+        assert_eq!(
+            escaped_tab.next(),
+            Some(Ok(JToken::String("a\tb".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_invalid_escapes() {
+        for json in [
+            r#""\x""#,
+            r#""\u12""#,
+            r#""\u12G4""#,
+            r#""\uD83D""#,
+            r#""\uDE00""#,
+            r#""\uD83Dx""#,
+        ] {
+            let mut t = Tokenizer::new(json.to_string());
+            assert_eq!(
+                t.next(),
+                Some(Err(TokenizeError::InvalidEscape(1))),
+                "{}",
+                json
+            );
+        }
+    }
+
+    #[test]
+    fn test_is_integer() {
+        let is_integer = |s: &str| s.parse::<Number>().unwrap().is_integer();
+
+        assert!(is_integer("5"));
+        assert!(is_integer("-120"));
+        assert!(!is_integer("5.0"));
+        assert!(!is_integer("0.25"));
+        assert!(!is_integer("1e3"));
+        assert!(!is_integer("2E-2"));
+        assert!(!Number::new_non_finite(f64::INFINITY).is_integer());
+    }
+
+    #[test]
+    fn test_as_f64() {
+        let as_f64 = |s: &str| s.parse::<Number>().unwrap().as_f64();
+
+        assert_eq!(as_f64("-12"), -12.0);
+        assert_eq!(as_f64("0.1"), 0.1);
+        assert_eq!(as_f64("2.5E-3"), 0.0025);
+        assert_eq!(Number::new(3, Some(0.5), Some(2)).as_f64(), 350.0);
+        assert!(Number::new_non_finite(f64::NAN).as_f64().is_nan());
+    }
+
+    #[test]
+    fn test_as_f64_out_of_range() {
+        let n = |s: &str| s.parse::<Number>().unwrap();
+
+        assert_eq!(n("1e400").as_f64(), f64::INFINITY);
+        assert_eq!(n("-1e400").as_f64(), f64::NEG_INFINITY);
+        assert_eq!(n("1e-400").as_f64(), 0.0);
+        assert!(n("-1e-400").as_f64().is_sign_negative());
+        assert_eq!(n("1e400").to_string(), "1e400");
+
+        assert_eq!(n("1e400").checked_as_f64(), None);
+        assert_eq!(n("-1e400").checked_as_f64(), None);
+        assert_eq!(n("1e-400").checked_as_f64(), None);
+        assert_eq!(Number::new(2, None, Some(-400)).checked_as_f64(), None);
+        assert_eq!(Number::new_non_finite(f64::INFINITY).checked_as_f64(), None);
+
+        assert_eq!(n("0e400").checked_as_f64(), Some(0.0));
+        assert_eq!(n("-0.0").checked_as_f64(), Some(0.0));
+        assert_eq!(n("1.5e300").checked_as_f64(), Some(1.5e300));
+        assert_eq!(n("5e-324").checked_as_f64(), Some(5e-324));
+    }
+
+    #[test]
+    fn test_mantissa_and_exponent_value() {
+        let n = |s: &str| s.parse::<Number>().unwrap();
+        let parts = |s: &str| (n(s).mantissa(), n(s).exponent_value());
+
+        assert_eq!(parts("1234"), (1.234, 3));
+        assert_eq!(parts("-0.0125"), (-1.25, -2));
+        assert_eq!(parts("7"), (7.0, 0));
+        assert_eq!(parts("0.0"), (0.0, 0));
+        assert_eq!(parts("1.5e10"), (1.5, 10));
+        assert_eq!(parts("-0.0125e3"), (-1.25, 1));
+        assert_eq!(parts("120E-5"), (1.2, -3));
+        assert_eq!(parts("1e400"), (1.0, 400));
+        let built = Number::new(25, None, Some(-1));
+        assert_eq!((built.mantissa(), built.exponent_value()), (2.5, 0));
+        let inf = Number::new_non_finite(f64::INFINITY);
+        assert_eq!((inf.mantissa(), inf.exponent_value()), (f64::INFINITY, 0));
+    }
+
+    #[test]
+    fn test_partial_ord() {
+        let n = |s: &str| s.parse::<Number>().unwrap();
+
+        assert!(n("-3") < n("2"));
+        assert!(n("1.5") < n("2"));
+        assert!(n("-0.25") > n("-0.5"));
+        assert!(n("1e3") > n("999.9"));
+        assert!(n("12E-1") < n("1.25"));
+        assert_eq!(n("5").partial_cmp(&n("5")), Some(Ordering::Equal));
+
+        // equal values, different forms
+        assert!(n("1") < n("1.0"));
+        assert!(n("1") < n("1e0"));
+
+        let mut numbers = ["10", "-1.5", "2e1", "0.001", "3"].map(n);
+        numbers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            numbers.map(|x| x.to_string()),
+            ["-1.5", "0.001", "3", "10", "2e1"]
+        );
+    }
+
+    #[test]
+    fn test_eq_is_exact() {
+        let n = |s: &str| s.parse::<Number>().unwrap();
+        let hash = |x: &Number| {
+            let mut h = std::collections::hash_map::DefaultHasher::new();
+            x.hash(&mut h);
+            h.finish()
+        };
+
+        let a = n("1000000000000000000000000000000000000001");
+        let b = n("1000000000000000000000000000000000000002");
+        assert_ne!(a, b);
+        assert!(a < b);
+        assert_ne!(n("0.1"), n("0.100000001"));
+        assert!(n("0.1") < n("0.100000001"));
+        assert!(n("-0.100000001") < n("-0.1"));
+
+        assert_eq!(n("1.0"), n("1.00"));
+        assert_eq!(hash(&n("1.0")), hash(&n("1.00")));
+        assert_eq!(n("-0"), n("0"));
+        assert_eq!(hash(&n("-0")), hash(&n("0")));
+        assert_eq!(n("1e2"), n("1E+2"));
+        assert_eq!(n("-1.0"), Number::new(-1, Some(0.0), None));
+    }
+
+    #[test]
+    fn test_exponent_overflow() {
+        for text in [
+            "1e99999999999",
+            "1.5E-99999999999",
+            "30000000000e99999999999",
+        ] {
+            let n = text.parse::<Number>().unwrap();
+            assert_eq!(n.to_string(), text);
+
+            let input = format!("[{}]", text);
+            for raw_numbers in [false, true] {
+                let options = ParserOptions {
+                    raw_numbers,
+                    ..Default::default()
+                };
+                let value = Parser::with_options(input.clone(), options).parse();
+                assert_eq!(value.map(|v| v.to_string()), Ok(input.clone()));
+            }
+        }
+        assert!("1e99999999999".parse::<Number>().unwrap() > "1e99999999998".parse().unwrap());
+        assert_eq!("1e".parse::<Number>(), Err(TokenizeError::InvalidNumber(0)));
+        assert_eq!(
+            "1e+".parse::<Number>(),
+            Err(TokenizeError::InvalidNumber(0))
+        );
+    }
+
+    #[test]
+    fn test_partial_ord_non_finite() {
+        let nan = Number::new_non_finite(f64::NAN);
+        let inf = Number::new_non_finite(f64::INFINITY);
+        let neg_inf = Number::new_non_finite(f64::NEG_INFINITY);
+        let one = Number::new(1, None, None);
+
+        assert!(neg_inf < one && one < inf);
+        assert_eq!(nan.partial_cmp(&nan), Some(Ordering::Equal));
+        assert_eq!(nan.partial_cmp(&one), None);
+        assert_eq!(inf.partial_cmp(&nan), None);
+    }
+
+    #[test]
+    fn test_max_input_len() {
+        let options = ParserOptions {
+            max_input_len: Some(8),
+            ..Default::default()
+        };
+        let mut fits = Tokenizer::with_options("[1, 2]".to_string(), options.clone());
+        let mut too_long = Tokenizer::with_options("[1, 2, 3, 4]".to_string(), options);
+
+        assert_eq!(fits.by_ref().filter(Result::is_err).count(), 0);
+        assert_eq!(too_long.next(), Some(Err(TokenizeError::InputTooLong(8))));
+        assert_eq!(too_long.next(), None);
+    }
+
+    #[test]
+    fn test_max_string_len() {
+        let options = ParserOptions {
+            max_string_len: Some(4),
+            ..Default::default()
+        };
+        let tokenize = |json: &str| {
+            Tokenizer::with_options(json.to_string(), options.clone()).find_map(Result::err)
+        };
+
+        assert_eq!(tokenize(r#"["abcd", "\n\t\/a", "éé"]"#), None);
+        assert_eq!(
+            tokenize(r#"["abcd", "abcde"]"#),
+            Some(TokenizeError::StringTooLong(9))
+        );
+        assert_eq!(
+            tokenize(r#"{"abcé": 1}"#),
+            Some(TokenizeError::StringTooLong(1))
+        );
+    }
+
+    #[test]
+    fn test_single_quotes() {
+        let options = ParserOptions {
+            allow_single_quotes: true,
+            ..Default::default()
+        };
+        let json = r#"{'key': 'it\'s "quoted"', "mixed": 'a\nb'}"#;
+        let tokens = Tokenizer::with_options(json.to_string(), options)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(tokens[1], JToken::String("key".to_string()));
+        assert_eq!(tokens[3], JToken::String("it's \"quoted\"".to_string()));
+        assert_eq!(tokens[5], JToken::String("mixed".to_string()));
+        assert_eq!(tokens[7], JToken::String("a\nb".to_string()));
+    }
+
+    #[test]
+    fn test_single_quotes_strict() {
+        let mut t = Tokenizer::new("['a']".to_string());
+        t.next();
+
+        assert_eq!(t.next(), Some(Err(TokenizeError::UnexpectedChar('\'', 1))));
+        assert_eq!(
+            Tokenizer::new(r#""it\'s""#.to_string()).next(),
+            Some(Err(TokenizeError::InvalidEscape(3)))
+        );
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let options = ParserOptions {
+            allow_unquoted_keys: true,
+            ..Default::default()
+        };
+        let tokens = Tokenizer::with_options("{_id: null, $x1: true}".to_string(), options)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+
+        assert_eq!(tokens[1], JToken::Ident("_id".to_string()));
+        assert_eq!(tokens[3], JToken::Null);
+        assert_eq!(tokens[5], JToken::Ident("$x1".to_string()));
+        assert_eq!(tokens[7], JToken::Bool(true));
+    }
+
+    #[test]
+    fn test_hex_numbers() {
+        let options = ParserOptions {
+            allow_hex_numbers: true,
+            ..Default::default()
+        };
+        let tokenize = |json: &str| {
+            Tokenizer::with_options(json.to_string(), options.clone())
+                .next()
+                .unwrap()
+        };
+
+        assert_eq!(
+            tokenize("0xFF"),
+            Ok(JToken::Number(Number::new(255, None, None)))
+        );
+        assert_eq!(
+            tokenize("0x0"),
+            Ok(JToken::Number(Number::new(0, None, None)))
+        );
+        assert_eq!(
+            tokenize("-0X1f"),
+            Ok(JToken::Number(Number::new(-31, None, None)))
+        );
+        assert_eq!(tokenize("0xG"), Err(TokenizeError::InvalidNumber(0)));
+        assert_eq!(
+            tokenize("0x100000000"),
+            Err(TokenizeError::InvalidNumber(0))
+        );
+        // written back as decimal, since hex is not JSON
+        match tokenize("0xff") {
+            Ok(JToken::Number(n)) => assert_eq!(n.to_string(), "255"),
+            token => panic!("unexpected {:?}", token),
+        }
+    }
+
+    #[test]
+    fn test_hex_numbers_strict() {
+        let tokens = Tokenizer::new("0xFF".to_string()).collect::<Vec<_>>();
+
+        assert_eq!(tokens[0], Ok(JToken::Number(Number::new(0, None, None))));
+        assert_eq!(
+            tokens[1],
+            Err(TokenizeError::InvalidKeyword("xFF".to_string(), 1))
+        );
+    }
+
+    const BIG: &str = "1234567890123456789012345678901234567890";
+
+    #[test]
+    fn test_big_integer() {
+        let n = BIG.parse::<Number>().unwrap();
+
+        assert_eq!(n.to_string(), BIG);
+        assert_eq!(n.as_f64(), 1.2345678901234568e39);
+        assert!(n.is_integer());
+
+        let negative = format!("-{}", BIG).parse::<Number>().unwrap();
+        assert_eq!(negative.to_string(), format!("-{}", BIG));
+        assert!(negative < n);
+    }
+
+    #[test]
+    fn test_big_numbers_in_documents() {
+        let input = format!(
+            "[{}, -{}.5e-3, 3.14159265358979323846264338327950288419716939937510]",
+            BIG, BIG
+        );
+        for raw_numbers in [false, true] {
+            let options = ParserOptions {
+                raw_numbers,
+                ..Default::default()
+            };
+            let value = Parser::with_options(input.clone(), options)
+                .parse()
+                .unwrap();
+
+            assert_eq!(value.to_string(), input.replace(' ', ""));
+            let JValue::Array(arr) = &value else {
+                unreachable!()
+            };
+            assert!(arr.iter().all(JValue::is_number));
+        }
+    }
+
+    #[test]
+    fn test_big_number_round_trips_binary() {
+        let value = Parser::new(format!("[{}]", BIG)).parse().unwrap();
+
+        assert_eq!(
+            JValue::from_binary(&value.to_binary()).unwrap().to_string(),
+            format!("[{}]", BIG)
+        );
+    }
+
+    #[test]
+    fn test_integers_beyond_i32() {
+        let input = "[3000000000, {\"ts\": 1700000000000}, -9223372036854775808]";
+        let value = Parser::new(input.to_string()).parse().unwrap();
+
+        assert_eq!(value.to_string(), input.replace(' ', ""));
+        let ts = value.pointer("/1/ts").and_then(JValue::as_number).unwrap();
+        assert!(ts.is_integer());
+        assert_eq!(ts.as_f64(), 1.7e12);
+        assert_eq!(ts.to_scaled_integer(), Some((1_700_000_000_000, 0)));
+        let min = value.pointer("/2").and_then(JValue::as_number).unwrap();
+        assert_eq!(min.to_scaled_integer(), Some((i64::MIN as i128, 0)));
+    }
+
+    #[test]
+    fn test_integers_beyond_f64() {
+        let digits = "7".repeat(400);
+        for text in [digits.clone(), format!("-{}.25", digits)] {
+            let n = text.parse::<Number>().unwrap();
+            assert_eq!(n.to_string(), text);
+            assert_eq!(n.exponent_value(), 399);
+            assert_eq!(n.checked_as_f64(), None);
+        }
+
+        let input = format!("[{}, {}1]", digits, digits);
+        let value = Parser::new(input.clone()).parse().unwrap();
+        assert_eq!(value.to_string(), input.replace(' ', ""));
+        let (a, b) = (value.pointer("/0").unwrap(), value.pointer("/1").unwrap());
+        assert!(a.as_number() < b.as_number());
+    }
 }
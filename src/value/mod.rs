@@ -0,0 +1,1618 @@
+use std::fmt::{self, Display, Write};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use indexmap::IndexMap;
+
+use crate::options::ParserOptions;
+use crate::parser::{JValue, ParseError, Parser};
+use crate::serializer::write_string;
+use crate::tokenizer::Number;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnflattenError {
+    /// The key names a leaf that another key also needs as a container, as
+    /// with `a` and `a.b`.
+    Conflict(String),
+}
+
+impl Display for UnflattenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnflattenError::Conflict(key) => {
+                write!(f, "key {:?} is both a value and a container", key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UnflattenError {}
+
+/// One step of a path for `JValue::get_path`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSegment<'a> {
+    /// A key of an object.
+    Key(&'a str),
+    /// An index into an array.
+    Index(usize),
+}
+
+/// Consistent with `PartialEq`: object entries are hashed independently of
+/// their order, and numbers by their exact text as `Number` compares them.
+impl Hash for JValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            JValue::Null => {}
+            JValue::Bool(b) => b.hash(state),
+            JValue::String(s) => s.hash(state),
+            JValue::Number(n) => n.hash(state),
+            JValue::Array(arr) => arr.hash(state),
+            JValue::Object(m) => {
+                let mut sum = 0u64;
+                for entry in m {
+                    let mut h = DefaultHasher::new();
+                    entry.hash(&mut h);
+                    sum = sum.wrapping_add(h.finish());
+                }
+                m.len().hash(state);
+                sum.hash(state);
+            }
+        }
+    }
+}
+
+impl JValue {
+    /// Name of the value's JSON type, for messages such as "expected object,
+    /// got array".
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            JValue::Null => "null",
+            JValue::Bool(_) => "bool",
+            JValue::String(_) => "string",
+            JValue::Number(_) => "number",
+            JValue::Array(_) => "array",
+            JValue::Object(_) => "object",
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, JValue::Null)
+    }
+
+    pub fn is_bool(&self) -> bool {
+        matches!(self, JValue::Bool(_))
+    }
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, JValue::String(_))
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, JValue::Number(_))
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self, JValue::Array(_))
+    }
+
+    pub fn is_object(&self) -> bool {
+        matches!(self, JValue::Object(_))
+    }
+
+    /// The number as parsed, with its source text and parts, if this is one.
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            JValue::Number(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    /// Value under `key`, if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&JValue> {
+        match self {
+            JValue::Object(m) => m.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut JValue> {
+        match self {
+            JValue::Object(m) => m.get_mut(key),
+            _ => None,
+        }
+    }
+
+    /// Element `index` of an array, or the value of the `index`-th entry of an
+    /// object in insertion order.
+    pub fn get_index(&self, index: usize) -> Option<&JValue> {
+        match self {
+            JValue::Array(arr) => arr.get(index),
+            JValue::Object(m) => m.get_index(index).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn get_index_mut(&mut self, index: usize) -> Option<&mut JValue> {
+        match self {
+            JValue::Array(arr) => arr.get_mut(index),
+            JValue::Object(m) => m.get_index_mut(index).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Looks up an RFC 6901 JSON Pointer such as `/items/0/name`. The empty
+    /// pointer refers to the value itself.
+    pub fn pointer(&self, ptr: &str) -> Option<&JValue> {
+        parse_pointer(ptr)?
+            .iter()
+            .try_fold(self, |v, token| v.child(token))
+    }
+
+    /// Like `pointer`, but falls back to `default` when nothing is there.
+    pub fn pointer_or<'a>(&'a self, ptr: &str, default: &'a JValue) -> &'a JValue {
+        self.pointer(ptr).unwrap_or(default)
+    }
+
+    /// Like `get`, but falls back to `default` when the key is missing or
+    /// this is not an object.
+    pub fn get_or<'a>(&'a self, key: &str, default: &'a JValue) -> &'a JValue {
+        self.get(key).unwrap_or(default)
+    }
+
+    /// Follows `path` one segment at a time, like `pointer` but without
+    /// building or unescaping a string. A key only matches in an object and
+    /// an index only in an array. The empty path refers to the value itself.
+    pub fn get_path(&self, path: &[PathSegment]) -> Option<&JValue> {
+        path.iter().try_fold(self, |v, segment| match (v, segment) {
+            (JValue::Object(m), PathSegment::Key(k)) => m.get(*k),
+            (JValue::Array(arr), PathSegment::Index(i)) => arr.get(*i),
+            _ => None,
+        })
+    }
+
+    pub fn pointer_mut(&mut self, ptr: &str) -> Option<&mut JValue> {
+        parse_pointer(ptr)?
+            .iter()
+            .try_fold(self, |v, token| v.child_mut(token))
+    }
+
+    /// Sets `key` on an object, returning the value it replaced. An existing
+    /// key keeps its position; a new one goes last. Does nothing and returns
+    /// `None` if this is not an object.
+    pub fn insert(&mut self, key: impl Into<String>, value: JValue) -> Option<JValue> {
+        match self {
+            JValue::Object(m) => m.insert(key.into(), value),
+            _ => None,
+        }
+    }
+
+    /// Removes `key` from an object, keeping the order of the other entries.
+    /// Returns `None` if the key is absent or this is not an object.
+    pub fn remove(&mut self, key: &str) -> Option<JValue> {
+        match self {
+            JValue::Object(m) => m.shift_remove(key),
+            _ => None,
+        }
+    }
+
+    /// Appends to an array. Does nothing if this is not an array.
+    pub fn push(&mut self, value: JValue) {
+        if let JValue::Array(arr) = self {
+            arr.push(value);
+        }
+    }
+
+    // the child a single pointer token refers to
+    pub(crate) fn child(&self, token: &str) -> Option<&JValue> {
+        match self {
+            JValue::Object(m) => m.get(token),
+            JValue::Array(arr) => arr.get(parse_index(token)?),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn child_mut(&mut self, token: &str) -> Option<&mut JValue> {
+        match self {
+            JValue::Object(m) => m.get_mut(token),
+            JValue::Array(arr) => arr.get_mut(parse_index(token)?),
+            _ => None,
+        }
+    }
+
+    /// Moves the value out, leaving `Null` in its place.
+    pub fn take(&mut self) -> JValue {
+        self.replace(JValue::Null)
+    }
+
+    /// Puts `value` in place of this one and returns the old value.
+    pub fn replace(&mut self, value: JValue) -> JValue {
+        std::mem::replace(self, value)
+    }
+
+    /// Number of entries of an object or elements of an array. Scalars have
+    /// none, so their length is 0.
+    pub fn len(&self) -> usize {
+        match self {
+            JValue::Array(arr) => arr.len(),
+            JValue::Object(m) => m.len(),
+            _ => 0,
+        }
+    }
+
+    /// True for empty arrays and objects, and for every scalar.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over an object's entries in order. Empty for non-objects.
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &JValue)> {
+        let m = match self {
+            JValue::Object(m) => Some(m),
+            _ => None,
+        };
+        m.into_iter().flatten()
+    }
+
+    /// An object's entries sorted by key, byte-wise, leaving the object's own
+    /// order as it is. Empty for non-objects.
+    pub fn sorted_entries(&self) -> Vec<(&String, &JValue)> {
+        let mut entries = self.entries().collect::<Vec<_>>();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    /// Iterates over an array's elements. Empty for non-arrays.
+    pub fn values_iter(&self) -> impl Iterator<Item = &JValue> {
+        let arr = match self {
+            JValue::Array(arr) => Some(arr),
+            _ => None,
+        };
+        arr.into_iter().flatten()
+    }
+
+    /// Splits an array into slices of `size` elements; the last may be
+    /// shorter. `None` for non-arrays or a `size` of zero.
+    pub fn array_chunks(&self, size: usize) -> Option<Vec<&[JValue]>> {
+        match self {
+            JValue::Array(arr) if size > 0 => Some(arr.chunks(size).collect()),
+            _ => None,
+        }
+    }
+
+    /// Visits every node depth-first in pre-order: a container is visited
+    /// before its children, object entries in insertion order and array
+    /// elements by index. Empty arrays and objects are visited like any other
+    /// node.
+    pub fn walk(&self, f: &mut impl FnMut(&JValue)) {
+        f(self);
+        match self {
+            JValue::Array(arr) => arr.iter().for_each(|v| v.walk(f)),
+            JValue::Object(m) => m.values().for_each(|v| v.walk(f)),
+            _ => {}
+        }
+    }
+
+    /// Mutable version of `walk`, in the same order. Children are visited
+    /// after `f` has run on their container, so replacements are descended into.
+    pub fn walk_mut(&mut self, f: &mut impl FnMut(&mut JValue)) {
+        f(self);
+        match self {
+            JValue::Array(arr) => arr.iter_mut().for_each(|v| v.walk_mut(f)),
+            JValue::Object(m) => m.values_mut().for_each(|v| v.walk_mut(f)),
+            _ => {}
+        }
+    }
+
+    /// Levels of nesting: 0 for a scalar, 1 for an array or object holding
+    /// only scalars or nothing at all, and one more for each level below.
+    pub fn depth(&self) -> usize {
+        // without recursing, as documents may nest deeper than the call
+        // stack allows
+        let mut deepest = 0;
+        let mut stack = vec![(self, 0)];
+        while let Some((value, level)) = stack.pop() {
+            let children: Box<dyn Iterator<Item = &JValue>> = match value {
+                JValue::Array(arr) => Box::new(arr.iter()),
+                JValue::Object(m) => Box::new(m.values()),
+                _ => continue,
+            };
+            deepest = deepest.max(level + 1);
+            stack.extend(children.map(|v| (v, level + 1)));
+        }
+        deepest
+    }
+
+    /// Replaces the value of every object key, at any depth, whose name
+    /// matches `predicate` with a copy of `replacement`. Replaced values are
+    /// not looked into.
+    pub fn redact(&mut self, predicate: impl Fn(&str) -> bool, replacement: JValue) {
+        redact_into(self, &predicate, &replacement);
+    }
+
+    /// Removes the entries of this object that `f` returns `false` for,
+    /// keeping the order of the rest. With `recursive`, objects at any depth
+    /// are pruned too, including inside arrays, and the contents of an entry
+    /// are pruned before `f` sees it.
+    pub fn retain(&mut self, recursive: bool, mut f: impl FnMut(&str, &JValue) -> bool) {
+        retain_into(self, recursive, &mut f);
+    }
+
+    /// Returns the entry of `allowed` equal to this string value, or `None`
+    /// if the value is not a string or not one of them.
+    pub fn as_enum<'a>(&self, allowed: &[&'a str]) -> Option<&'a str> {
+        match self {
+            JValue::String(s) => allowed.iter().find(|&&a| a == s).copied(),
+            _ => None,
+        }
+    }
+
+    /// Compares two values as JSON data. Object key order is ignored (as it is
+    /// by `==`), array order is respected, and numbers are compared by value,
+    /// so `1`, `1.0` and `1e0` are all equal.
+    pub fn semantic_eq(&self, other: &JValue) -> bool {
+        match (self, other) {
+            (JValue::Number(a), JValue::Number(b)) => {
+                match (a.to_scaled_integer(), b.to_scaled_integer()) {
+                    (Some(x), Some(y)) => normalize_scaled(x) == normalize_scaled(y),
+                    // too long for an `i128`, so by significant digits and exponent
+                    _ if a.is_finite() && b.is_finite() => a.scientific() == b.scientific(),
+                    _ => a == b,
+                }
+            }
+            (JValue::Array(a), JValue::Array(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(x, y)| x.semantic_eq(y))
+            }
+            (JValue::Object(a), JValue::Object(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(k, v)| b.get(k).is_some_and(|w| v.semantic_eq(w)))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Hashes each top-level field of an object, so two versions of a document
+    /// can be compared field by field. Hashes follow `Hash for JValue`, which
+    /// ignores object key order; they are stable across runs but not
+    /// guaranteed across Rust releases. Non-objects have no fields.
+    pub fn field_checksums(&self) -> IndexMap<String, u64> {
+        let JValue::Object(m) = self else {
+            return IndexMap::new();
+        };
+        m.iter()
+            .map(|(k, v)| {
+                let mut h = DefaultHasher::new();
+                v.hash(&mut h);
+                (k.clone(), h.finish())
+            })
+            .collect()
+    }
+
+    /// Hash of the value's content that, unlike `Hash for JValue`, is stable
+    /// across runs, platforms and Rust releases, so it can be stored. It is
+    /// taken over a canonical form with object keys sorted and numbers reduced
+    /// to their value, so values that are `semantic_eq` hash alike.
+    pub fn content_hash(&self) -> u64 {
+        let mut h = Fnv1a(FNV_OFFSET_BASIS);
+        // the hasher never fails to take input
+        let _ = write_content(&mut h, self);
+        h.0
+    }
+
+    /// Reads a duration string such as `"30s"`, `"5m"`, `"2h"` or `"1d"` as
+    /// seconds. A bare number is taken as seconds.
+    pub fn as_duration_secs(&self) -> Option<u64> {
+        let JValue::String(s) = self else {
+            return None;
+        };
+        let (n, unit) = split_unit(s)?;
+        let scale = match unit {
+            "" | "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => return None,
+        };
+        n.checked_mul(scale)
+    }
+
+    /// Reads a size string such as `"512B"`, `"2KB"` or `"10MB"` as bytes,
+    /// using binary multiples (1KB = 1024 bytes). Units are case-insensitive
+    /// and a bare number is taken as bytes.
+    pub fn as_byte_size(&self) -> Option<u64> {
+        let JValue::String(s) = self else {
+            return None;
+        };
+        let (n, unit) = split_unit(s)?;
+        let exp = match unit.to_ascii_uppercase().as_str() {
+            "" | "B" => 0,
+            "KB" => 1,
+            "MB" => 2,
+            "GB" => 3,
+            "TB" => 4,
+            _ => return None,
+        };
+        n.checked_mul(1024u64.pow(exp))
+    }
+
+    /// Parses `s` and returns the JSON Pointer of every key that repeats within
+    /// its object, anywhere in the document; empty if there are none. Works on
+    /// source text because a parsed tree has already collapsed duplicates.
+    pub fn validate_no_duplicate_keys_deep(s: String) -> Result<Vec<String>, ParseError> {
+        let options = ParserOptions {
+            record_duplicate_keys: true,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(s, options);
+        parser.parse()?;
+        Ok(parser.duplicate_keys().to_vec())
+    }
+
+    /// Finds the JSON Pointer of `target` inside this tree. Nodes are matched by
+    /// address, not by value, so `target` must be a reference into `self`.
+    pub fn path_of(&self, target: &JValue) -> Option<String> {
+        if std::ptr::eq(self, target) {
+            return Some("".to_string());
+        }
+
+        match self {
+            JValue::Array(arr) => arr
+                .iter()
+                .enumerate()
+                .find_map(|(i, v)| v.path_of(target).map(|p| format!("/{}{}", i, p))),
+            JValue::Object(m) => m.iter().find_map(|(k, v)| {
+                v.path_of(target)
+                    .map(|p| format!("/{}{}", escape_pointer_token(k), p))
+            }),
+            _ => None,
+        }
+    }
+
+    /// Folds over the elements of an array, stopping at the first `Err`.
+    /// Values other than arrays have no elements, so `init` is returned as is.
+    pub fn try_fold<B, E>(
+        &self,
+        init: B,
+        f: impl FnMut(B, &JValue) -> Result<B, E>,
+    ) -> Result<B, E> {
+        match self {
+            JValue::Array(arr) => arr.iter().try_fold(init, f),
+            _ => Ok(init),
+        }
+    }
+
+    /// Applies `f` to each pair of elements of two arrays of equal length.
+    /// Returns `None` if either value is not an array or the lengths differ.
+    pub fn zip_merge(
+        &self,
+        other: &JValue,
+        f: impl Fn(&JValue, &JValue) -> JValue,
+    ) -> Option<JValue> {
+        match (self, other) {
+            (JValue::Array(a), JValue::Array(b)) if a.len() == b.len() => Some(JValue::Array(
+                a.iter().zip(b).map(|(x, y)| f(x, y)).collect(),
+            )),
+            _ => None,
+        }
+    }
+
+    /// Appends the elements of `other` when both are arrays; does nothing
+    /// otherwise. With `dedup`, elements `semantic_eq` to one already in the
+    /// array, including one appended just before, are skipped. Elements
+    /// already in `self` are kept as they are.
+    pub fn concat_array(&mut self, other: &JValue, dedup: bool) {
+        let (JValue::Array(arr), JValue::Array(more)) = (self, other) else {
+            return;
+        };
+        for v in more {
+            if !dedup || !arr.iter().any(|x| x.semantic_eq(v)) {
+                arr.push(v.clone());
+            }
+        }
+    }
+
+    /// Flattens nested objects and arrays into one map from dotted paths to
+    /// leaf values, so `{"a": {"b": [1]}}` becomes `{"a.b.0": 1}`. Leaves are
+    /// scalars and empty arrays and objects, so `{"a": []}` stays
+    /// `{"a": []}` and `unflatten` can restore it. A scalar maps to `""`; an
+    /// empty array or object has no entries.
+    pub fn flatten(&self) -> IndexMap<String, JValue> {
+        self.flatten_with('.')
+    }
+
+    /// Same as `flatten`, joining path segments with `sep`.
+    pub fn flatten_with(&self, sep: char) -> IndexMap<String, JValue> {
+        let mut out = IndexMap::new();
+        if !is_empty_container(self) {
+            flatten_into(self, &mut String::new(), sep, &mut out);
+        }
+        out
+    }
+
+    /// JSON Pointers of every leaf in the tree, in document order. Leaves are
+    /// as for `flatten`: scalars, and empty arrays and objects other than the
+    /// value itself.
+    pub fn leaf_paths(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        if !is_empty_container(self) {
+            collect_leaf_paths(self, &mut String::new(), &mut paths);
+        }
+        paths
+    }
+
+    /// Inverse of `flatten_with`: splits every key on `sep` and nests the
+    /// values accordingly. Objects whose keys come out as `0`, `1`, ... in
+    /// order are rebuilt as arrays. Errors if a key is both a leaf and a
+    /// prefix of another key.
+    pub fn unflatten(map: &IndexMap<String, JValue>, sep: char) -> Result<JValue, UnflattenError> {
+        let mut root = IndexMap::new();
+        for (key, value) in map {
+            let conflict = || UnflattenError::Conflict(key.clone());
+            let mut segments = key.split(sep).peekable();
+            let mut m = &mut root;
+            while let Some(segment) = segments.next() {
+                if segments.peek().is_none() {
+                    if m.contains_key(segment) {
+                        return Err(conflict());
+                    }
+                    m.insert(segment.to_string(), value.clone());
+                    break;
+                }
+                let child = m
+                    .entry(segment.to_string())
+                    .or_insert_with(|| JValue::Object(IndexMap::new()));
+                match child {
+                    JValue::Object(child) => m = child,
+                    _ => return Err(conflict()),
+                }
+            }
+        }
+        let mut root = JValue::Object(root);
+        restore_arrays(&mut root);
+        Ok(root)
+    }
+}
+
+fn redact_into(value: &mut JValue, predicate: &impl Fn(&str) -> bool, replacement: &JValue) {
+    match value {
+        JValue::Array(arr) => arr
+            .iter_mut()
+            .for_each(|v| redact_into(v, predicate, replacement)),
+        JValue::Object(m) => {
+            for (k, v) in m.iter_mut() {
+                if predicate(k) {
+                    *v = replacement.clone();
+                } else {
+                    redact_into(v, predicate, replacement);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn retain_into(value: &mut JValue, recursive: bool, f: &mut impl FnMut(&str, &JValue) -> bool) {
+    match value {
+        JValue::Array(arr) if recursive => arr.iter_mut().for_each(|v| retain_into(v, true, f)),
+        JValue::Object(m) => {
+            if recursive {
+                m.values_mut().for_each(|v| retain_into(v, true, f));
+            }
+            m.retain(|k, v| f(k, v));
+        }
+        _ => {}
+    }
+}
+
+fn flatten_into(value: &JValue, path: &mut String, sep: char, out: &mut IndexMap<String, JValue>) {
+    let mut descend = |segment: &str, v: &JValue| {
+        let len = path.len();
+        if len > 0 {
+            path.push(sep);
+        }
+        path.push_str(segment);
+        flatten_into(v, path, sep, out);
+        path.truncate(len);
+    };
+    match value {
+        JValue::Array(arr) if !arr.is_empty() => {
+            for (i, v) in arr.iter().enumerate() {
+                descend(&i.to_string(), v);
+            }
+        }
+        JValue::Object(m) if !m.is_empty() => {
+            for (k, v) in m {
+                descend(k, v);
+            }
+        }
+        leaf => {
+            out.insert(path.clone(), leaf.clone());
+        }
+    }
+}
+
+fn is_empty_container(value: &JValue) -> bool {
+    matches!(value, JValue::Array(arr) if arr.is_empty())
+        || matches!(value, JValue::Object(m) if m.is_empty())
+}
+
+fn collect_leaf_paths(value: &JValue, path: &mut String, paths: &mut Vec<String>) {
+    let len = path.len();
+    match value {
+        JValue::Array(arr) if !arr.is_empty() => {
+            for (i, v) in arr.iter().enumerate() {
+                path.push_str(&format!("/{}", i));
+                collect_leaf_paths(v, path, paths);
+                path.truncate(len);
+            }
+        }
+        JValue::Object(m) if !m.is_empty() => {
+            for (k, v) in m {
+                path.push_str(&format!("/{}", escape_pointer_token(k)));
+                collect_leaf_paths(v, path, paths);
+                path.truncate(len);
+            }
+        }
+        _ => paths.push(path.clone()),
+    }
+}
+
+// turns objects keyed "0", "1", ... back into arrays, innermost first
+fn restore_arrays(value: &mut JValue) {
+    let JValue::Object(m) = value else {
+        return;
+    };
+    m.values_mut().for_each(restore_arrays);
+    if !m.is_empty() && m.keys().enumerate().all(|(i, k)| *k == i.to_string()) {
+        *value = JValue::Array(std::mem::take(m).into_values().collect());
+    }
+}
+
+// splits "10MB" into (10, "MB")
+fn split_unit(s: &str) -> Option<(u64, &str)> {
+    let s = s.trim();
+    let i = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    if i == 0 {
+        return None;
+    }
+    Some((s[..i].parse().ok()?, s[i..].trim_start()))
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+// 64-bit FNV-1a, fixed by its spec rather than by the standard library
+struct Fnv1a(u64);
+
+impl Write for Fnv1a {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for b in s.bytes() {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(FNV_PRIME);
+        }
+        Ok(())
+    }
+}
+
+// the canonical form `content_hash` is taken over: compact JSON with keys
+// sorted by their bytes and numbers written as `coefficient` or
+// `coefficient e-scale` in lowest terms
+fn write_content(out: &mut impl Write, value: &JValue) -> fmt::Result {
+    match value {
+        JValue::Number(n) => write_exact_number(out, n),
+        JValue::Array(arr) => {
+            out.write_char('[')?;
+            for (i, v) in arr.iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_content(out, v)?;
+            }
+            out.write_char(']')
+        }
+        JValue::Object(m) => {
+            let mut entries = m.iter().collect::<Vec<_>>();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            out.write_char('{')?;
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.write_char(',')?;
+                }
+                write_string(out, k)?;
+                out.write_char(':')?;
+                write_content(out, v)?;
+            }
+            out.write_char('}')
+        }
+        scalar => write!(out, "{}", scalar),
+    }
+}
+
+// the exact value as `coefficient` or `coefficient` e-`scale` with no trailing
+// zeros in the coefficient, as `normalize_scaled` leaves it; a coefficient
+// too long for an `i128` keeps its positive exponent instead of the zeros
+fn write_exact_number(out: &mut impl Write, n: &Number) -> fmt::Result {
+    if !n.is_finite() {
+        return write!(out, "{}", n);
+    }
+    let (negative, digits, exponent) = n.scientific();
+    if digits.is_empty() {
+        return out.write_char('0');
+    }
+    if negative {
+        out.write_char('-')?;
+    }
+    out.write_str(&digits)?;
+    let shift = exponent.saturating_sub(digits.len() as i64 - 1);
+    match shift {
+        0 => Ok(()),
+        ..0 => write!(out, "e{}", shift),
+        _ if digits.len() as i64 + shift <= 38 => out.write_str(&"0".repeat(shift as usize)),
+        _ => write!(out, "e{}", shift),
+    }
+}
+
+// strips trailing zeros so that equal values get equal (coefficient, scale) pairs
+fn normalize_scaled((mut coefficient, mut scale): (i128, i32)) -> (i128, i32) {
+    while scale > 0 && coefficient % 10 == 0 {
+        coefficient /= 10;
+        scale -= 1;
+    }
+    (coefficient, scale)
+}
+
+pub(crate) fn escape_pointer_token(s: &str) -> String {
+    s.replace('~', "~0").replace('/', "~1")
+}
+
+/// Splits an RFC 6901 JSON Pointer into unescaped reference tokens. Returns
+/// `None` if the pointer is neither empty nor starts with `/`.
+pub(crate) fn parse_pointer(ptr: &str) -> Option<Vec<String>> {
+    if ptr.is_empty() {
+        return Some(vec![]);
+    }
+    let rest = ptr.strip_prefix('/')?;
+    Some(
+        rest.split('/')
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
+/// Parses an array index token: decimal digits without leading zeros.
+pub(crate) fn parse_index(token: &str) -> Option<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) {
+        return None;
+    }
+    if !token.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_as_number() {
+        let value = Parser::new("[-12.50e3, \"1\"]".to_string())
+            .parse()
+            .unwrap();
+        let n = value.get_index(0).and_then(JValue::as_number).unwrap();
+
+        assert_eq!(n.to_string(), "-12.50e3");
+        assert_eq!((n.int, n.frac, n.exponent), (-12, Some(0.5), Some(3)));
+        assert_eq!((n.mantissa(), n.exponent_value()), (-1.25, 4));
+        assert_eq!(value.get_index(1).and_then(JValue::as_number), None);
+    }
+
+    #[test]
+    fn test_predicates() {
+        let value = Parser::new("[null, true, \"s\", 1.5, [], {}]".to_string())
+            .parse()
+            .unwrap();
+        let JValue::Array(arr) = &value else {
+            unreachable!()
+        };
+        let predicates: [fn(&JValue) -> bool; 6] = [
+            JValue::is_null,
+            JValue::is_bool,
+            JValue::is_string,
+            JValue::is_number,
+            JValue::is_array,
+            JValue::is_object,
+        ];
+
+        for (i, v) in arr.iter().enumerate() {
+            for (j, p) in predicates.iter().enumerate() {
+                assert_eq!(p(v), i == j, "value {} predicate {}", i, j);
+            }
+        }
+    }
+
+    #[test]
+    fn test_type_name() {
+        let value = Parser::new("[null, false, \"s\", -1e3, [], {}]".to_string())
+            .parse()
+            .unwrap();
+        let JValue::Array(arr) = &value else {
+            unreachable!()
+        };
+
+        assert_eq!(
+            arr.iter().map(JValue::type_name).collect::<Vec<_>>(),
+            ["null", "bool", "string", "number", "array", "object"]
+        );
+    }
+
+    #[test]
+    fn test_entries() {
+        let value = Parser::new("{\"a\": 1, \"b\": \"x\", \"c\": 20}".to_string())
+            .parse()
+            .unwrap();
+        let sum = value
+            .entries()
+            .filter_map(|(_, v)| match v {
+                JValue::Number(n) => n.to_scaled_integer(),
+                _ => None,
+            })
+            .map(|(c, _)| c)
+            .sum::<i128>();
+
+        assert_eq!(sum, 21);
+        assert_eq!(
+            value.entries().map(|(k, _)| k.as_str()).collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        assert_eq!(JValue::Null.entries().count(), 0);
+    }
+
+    #[test]
+    fn test_sorted_entries() {
+        let value = Parser::new("{\"c\": 3, \"a\": 1, \"B\": 0, \"b\": [2]}".to_string())
+            .parse()
+            .unwrap();
+        let sorted = value
+            .sorted_entries()
+            .into_iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>();
+
+        assert_eq!(sorted, ["B=0", "a=1", "b=[2]", "c=3"]);
+        assert_eq!(value.to_string(), "{\"c\":3,\"a\":1,\"B\":0,\"b\":[2]}");
+        assert!(JValue::Null.sorted_entries().is_empty());
+    }
+
+    #[test]
+    fn test_values_iter() {
+        let value = Parser::new("[null, true, {}]".to_string()).parse().unwrap();
+
+        assert_eq!(value.values_iter().count(), 3);
+        assert!(value.values_iter().next().unwrap().is_null());
+        assert_eq!(JValue::Bool(true).values_iter().count(), 0);
+    }
+
+    #[test]
+    fn test_array_chunks() {
+        let value = Parser::new("[1, 2, 3, 4, 5, 6, 7]".to_string())
+            .parse()
+            .unwrap();
+        let chunks = value.array_chunks(3).unwrap();
+
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>(),
+            [3, 3, 1]
+        );
+        assert_eq!(JValue::Array(chunks[2].to_vec()).to_string(), "[7]");
+        assert_eq!(value.array_chunks(0), None);
+        assert_eq!(JValue::Null.array_chunks(3), None);
+    }
+
+    #[test]
+    fn test_walk() {
+        let value = Parser::new("{\"a\": [1, {\"b\": null}], \"c\": \"x\"}".to_string())
+            .parse()
+            .unwrap();
+        let mut count = 0;
+        value.walk(&mut |_| count += 1);
+        assert_eq!(count, 6);
+
+        let mut kinds = vec![];
+        value.walk(&mut |v| kinds.push(v.is_array() || v.is_object()));
+        assert_eq!(kinds, [true, true, false, true, false, false]);
+    }
+
+    #[test]
+    fn test_walk_mut() {
+        let mut value = Parser::new("{\"A\": [\"Foo\", {\"B\": \"BAR\"}]}".to_string())
+            .parse()
+            .unwrap();
+        value.walk_mut(&mut |v| {
+            if let JValue::String(s) = v {
+                *s = s.to_lowercase();
+            }
+        });
+
+        assert_eq!(value.to_string(), "{\"A\":[\"foo\",{\"B\":\"bar\"}]}");
+    }
+
+    #[test]
+    fn test_concat_array() {
+        let parse = |s: &str| Parser::new(s.to_string()).parse().unwrap();
+        let other = parse("[2, {\"b\": 1, \"a\": 1.0}, 2.0, \"x\"]");
+
+        let mut value = parse("[1, 2, {\"a\": 1, \"b\": 1}]");
+        value.concat_array(&other, false);
+        assert_eq!(
+            value.to_string(),
+            "[1,2,{\"a\":1,\"b\":1},2,{\"b\":1,\"a\":1.0},2.0,\"x\"]"
+        );
+
+        let mut value = parse("[1, 2, {\"a\": 1, \"b\": 1}]");
+        value.concat_array(&other, true);
+        assert_eq!(value.to_string(), "[1,2,{\"a\":1,\"b\":1},\"x\"]");
+
+        // duplicates within `other` are dropped too
+        let mut value = parse("[]");
+        value.concat_array(&parse("[1, 1, 1e0]"), true);
+        assert_eq!(value.to_string(), "[1]");
+
+        let mut value = parse("{\"a\": 1}");
+        value.concat_array(&other, false);
+        assert_eq!(value.to_string(), "{\"a\":1}");
+        let mut value = parse("[1]");
+        value.concat_array(&JValue::Null, false);
+        assert_eq!(value.to_string(), "[1]");
+    }
+
+    #[test]
+    fn test_redact() {
+        let mut value = Parser::new(
+            "{\"user\": \"ann\", \"password\": \"hunter2\", \"sessions\": [{\"id\": 1, \"password\": {\"old\": \"x\"}}], \"auth\": {\"api_token\": \"abc\", \"scope\": \"read\"}}"
+                .to_string(),
+        )
+        .parse()
+        .unwrap();
+        value.redact(
+            |k| k.contains("password") || k.contains("token"),
+            JValue::String("[redacted]".to_string()),
+        );
+
+        assert_eq!(
+            value.to_string(),
+            "{\"user\":\"ann\",\"password\":\"[redacted]\",\"sessions\":[{\"id\":1,\"password\":\"[redacted]\"}],\"auth\":{\"api_token\":\"[redacted]\",\"scope\":\"read\"}}"
+        );
+    }
+
+    #[test]
+    fn test_redact_replacement_not_descended() {
+        let mut value = Parser::new("{\"secret\": 1}".to_string()).parse().unwrap();
+        let replacement = Parser::new("{\"secret\": null}".to_string())
+            .parse()
+            .unwrap();
+        value.redact(|k| k == "secret", replacement);
+
+        assert_eq!(value.to_string(), "{\"secret\":{\"secret\":null}}");
+    }
+
+    #[test]
+    fn test_retain() {
+        let input = "{\"a\": null, \"b\": {\"c\": null, \"d\": 1}, \"e\": [{\"f\": null}, null], \"g\": {\"h\": null}}";
+        let mut value = Parser::new(input.to_string()).parse().unwrap();
+        let mut shallow = value.clone();
+
+        value.retain(true, |_, v| !v.is_null());
+        assert_eq!(
+            value.to_string(),
+            "{\"b\":{\"d\":1},\"e\":[{},null],\"g\":{}}"
+        );
+
+        shallow.retain(false, |_, v| !v.is_null());
+        assert_eq!(
+            shallow.to_string(),
+            "{\"b\":{\"c\":null,\"d\":1},\"e\":[{\"f\":null},null],\"g\":{\"h\":null}}"
+        );
+
+        // entries are judged after their contents are pruned
+        let mut value = Parser::new(input.to_string()).parse().unwrap();
+        value.retain(true, |_, v| !(v.is_null() || v.is_object() && v.is_empty()));
+        assert_eq!(value.to_string(), "{\"b\":{\"d\":1},\"e\":[{},null]}");
+    }
+
+    #[test]
+    fn test_as_enum() {
+        let allowed = ["active", "suspended", "deleted"];
+        let status = JValue::String("suspended".to_string());
+
+        assert_eq!(status.as_enum(&allowed), Some("suspended"));
+        assert_eq!(
+            JValue::String("archived".to_string()).as_enum(&allowed),
+            None
+        );
+        assert_eq!(JValue::Null.as_enum(&allowed), None);
+    }
+
+    #[test]
+    fn test_semantic_eq_key_order() {
+        let a = Parser::new("{\"a\": 1, \"b\": {\"c\": [1, 2], \"d\": null}}".to_string())
+            .parse()
+            .unwrap();
+        let b = Parser::new("{\"b\": {\"d\": null, \"c\": [1, 2]}, \"a\": 1}".to_string())
+            .parse()
+            .unwrap();
+        let c = Parser::new("{\"b\": {\"d\": null, \"c\": [2, 1]}, \"a\": 1}".to_string())
+            .parse()
+            .unwrap();
+
+        assert!(a.semantic_eq(&b));
+        assert!(!a.semantic_eq(&c));
+    }
+
+    #[test]
+    fn test_semantic_eq_numbers() {
+        let a = Parser::new("[1, 0.5, 1500]".to_string()).parse().unwrap();
+        let b = Parser::new("[1.0, 5e-1, 1.5E3]".to_string())
+            .parse()
+            .unwrap();
+
+        assert!(a.semantic_eq(&b));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_semantic_eq_large_numbers() {
+        let n = |s: &str| Parser::new(format!("[{}]", s)).parse().unwrap();
+
+        assert!(!n("1e400").semantic_eq(&n("1")));
+        assert!(n("1e400").semantic_eq(&n("10.0e399")));
+        assert!(!n("1e400").semantic_eq(&n("1e401")));
+        assert!(!n("1e-400").semantic_eq(&n("0")));
+        let digits = "1".repeat(45);
+        assert!(n(&digits).semantic_eq(&n(&format!("{}.000", digits))));
+        assert!(!n(&digits).semantic_eq(&n(&format!("{}2", &digits[1..]))));
+    }
+
+    #[test]
+    fn test_field_checksums() {
+        let v1 = Parser::new(
+            "{\"a\": 1, \"b\": {\"x\": [1, 2], \"y\": null}, \"c\": \"s\"}".to_string(),
+        )
+        .parse()
+        .unwrap();
+        let v2 = Parser::new(
+            "{\"a\": 1, \"b\": {\"y\": null, \"x\": [1, 2]}, \"c\": \"t\"}".to_string(),
+        )
+        .parse()
+        .unwrap();
+        let (c1, c2) = (v1.field_checksums(), v2.field_checksums());
+
+        let changed = c1.keys().filter(|k| c1[*k] != c2[*k]).collect::<Vec<_>>();
+        assert_eq!(changed, ["c"]);
+        assert!(JValue::Null.field_checksums().is_empty());
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let a = Parser::new("{\"a\": 1, \"b\": {\"x\": [1, 2], \"y\": null}}".to_string())
+            .parse()
+            .unwrap();
+        let b = Parser::new("{\"b\": {\"y\": null, \"x\": [1.0, 2e0]}, \"a\": 1}".to_string())
+            .parse()
+            .unwrap();
+        let c = Parser::new("{\"a\": 1, \"b\": {\"x\": [2, 1], \"y\": null}}".to_string())
+            .parse()
+            .unwrap();
+
+        assert_eq!(a.content_hash(), b.content_hash());
+        assert_ne!(a.content_hash(), c.content_hash());
+        // fixed by the algorithm, not by the build
+        assert_eq!(JValue::Null.content_hash(), 0x5b9b_c4ba_5281_08e4);
+    }
+
+    #[test]
+    fn test_numbers_hash_exactly() {
+        // equal as f32 parts, different as text
+        let pairs = [
+            ("0.1", "0.100000001"),
+            (
+                "1000000000000000000000000000000000000001",
+                "1000000000000000000000000000000000000002",
+            ),
+        ];
+        for (a, b) in pairs {
+            let a = Parser::new(format!("[{}]", a)).parse().unwrap();
+            let b = Parser::new(format!("[{}]", b)).parse().unwrap();
+            assert_ne!(a, b);
+            assert_eq!(HashSet::from([a.clone(), b.clone()]).len(), 2);
+            assert_ne!(a.content_hash(), b.content_hash());
+        }
+
+        let long = Parser::new(format!("[1.{}]", "0".repeat(60)))
+            .parse()
+            .unwrap();
+        let short = Parser::new("[1]".to_string()).parse().unwrap();
+        assert_eq!(long.content_hash(), short.content_hash());
+        let big = Parser::new("[1e400]".to_string()).parse().unwrap();
+        let same = Parser::new("[10.0e399]".to_string()).parse().unwrap();
+        assert_eq!(big.content_hash(), same.content_hash());
+    }
+
+    #[test]
+    fn test_as_duration_secs() {
+        let d = |s: &str| JValue::String(s.to_string()).as_duration_secs();
+
+        assert_eq!(d("30s"), Some(30));
+        assert_eq!(d("5m"), Some(300));
+        assert_eq!(d("2h"), Some(7200));
+        assert_eq!(d("1d"), Some(86400));
+        assert_eq!(d("45"), Some(45));
+        assert_eq!(d("5 parsecs"), None);
+        assert_eq!(d("s"), None);
+        assert_eq!(JValue::Null.as_duration_secs(), None);
+    }
+
+    #[test]
+    fn test_as_byte_size() {
+        let b = |s: &str| JValue::String(s.to_string()).as_byte_size();
+
+        assert_eq!(b("2KB"), Some(2048));
+        assert_eq!(b("10MB"), Some(10 * 1024 * 1024));
+        assert_eq!(b("1 gb"), Some(1 << 30));
+        assert_eq!(b("512"), Some(512));
+        assert_eq!(b("99999999999TB"), None);
+        assert_eq!(b("2XB"), None);
+    }
+
+    #[test]
+    fn test_path_of() {
+        let input = "{\"a\": {\"b/c\": [1, {\"d\": true}]}}".to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let target = match &value {
+            JValue::Object(m) => match &m["a"] {
+                JValue::Object(mm) => match &mm["b/c"] {
+                    JValue::Array(arr) => &arr[1],
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+
+        assert_eq!(value.path_of(target), Some("/a/b~1c/1".to_string()));
+        assert_eq!(value.path_of(&value), Some("".to_string()));
+    }
+
+    #[test]
+    fn test_hash_set_dedup() {
+        let a = Parser::new("{\"a\": [1, 2.5], \"b\": null}".to_string())
+            .parse()
+            .unwrap();
+        let b = Parser::new("{\"b\": null, \"a\": [1, 2.50]}".to_string())
+            .parse()
+            .unwrap();
+        let c = Parser::new("{\"a\": [2.5, 1], \"b\": null}".to_string())
+            .parse()
+            .unwrap();
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        set.insert(b);
+        assert_eq!(set.len(), 1);
+        set.insert(c);
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_zip_merge() {
+        let a = Parser::new("[1, 2, 3]".to_string()).parse().unwrap();
+        let b = Parser::new("[10, 20, 30]".to_string()).parse().unwrap();
+        let sum = |x: &JValue, y: &JValue| match (x, y) {
+            (JValue::Number(x), JValue::Number(y)) => {
+                let (x, _) = x.to_scaled_integer().unwrap();
+                let (y, _) = y.to_scaled_integer().unwrap();
+                JValue::Number(Number::new((x + y) as i32, None, None))
+            }
+            _ => JValue::Null,
+        };
+
+        assert_eq!(a.zip_merge(&b, sum).unwrap().to_string(), "[11,22,33]");
+    }
+
+    #[test]
+    fn test_zip_merge_mismatch() {
+        let a = Parser::new("[1, 2]".to_string()).parse().unwrap();
+        let b = Parser::new("[1]".to_string()).parse().unwrap();
+        let c = Parser::new("{}".to_string()).parse().unwrap();
+
+        assert_eq!(a.zip_merge(&b, |x, _| x.clone()), None);
+        assert_eq!(a.zip_merge(&c, |x, _| x.clone()), None);
+    }
+
+    #[test]
+    fn test_try_fold() {
+        let sum = |acc: i128, v: &JValue| match v {
+            JValue::Number(n) => match n.to_scaled_integer() {
+                Some((c, 0)) => Ok(acc + c),
+                _ => Err(format!("not an integer: {}", n)),
+            },
+            v => Err(format!("not a number: {}", v)),
+        };
+
+        let value = Parser::new("[1, 2, 3, 4]".to_string()).parse().unwrap();
+        assert_eq!(value.try_fold(0, sum), Ok(10));
+
+        let value = Parser::new("[1, \"two\", 3]".to_string()).parse().unwrap();
+        assert_eq!(
+            value.try_fold(0, sum),
+            Err("not a number: \"two\"".to_string())
+        );
+
+        let value = Parser::new("{\"a\": 1}".to_string()).parse().unwrap();
+        assert_eq!(value.try_fold(5, sum), Ok(5));
+    }
+
+    #[test]
+    fn test_path_of_equal_but_foreign() {
+        let input = "[1, 2]".to_string();
+        let value = Parser::new(input).parse().unwrap();
+        let other = JValue::Number(Number::new(1, None, None));
+
+        assert_eq!(value.path_of(&other), None);
+    }
+
+    #[test]
+    fn test_validate_no_duplicate_keys_deep() {
+        let nested = "{\"id\": 1, \"items\": [{\"k\": 1}, {\"k\": 2, \"k\": 3}]}";
+        let clean = "{\"id\": 1, \"items\": [{\"k\": 1}, {\"k\": 2}]}";
+
+        assert_eq!(
+            JValue::validate_no_duplicate_keys_deep(nested.to_string()).unwrap(),
+            ["/items/1/k"]
+        );
+        assert!(JValue::validate_no_duplicate_keys_deep(clean.to_string())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_len() {
+        let value = Parser::new("{\"a\": [1, 2, 3], \"b\": {}, \"c\": \"xyz\"}".to_string())
+            .parse()
+            .unwrap();
+
+        assert_eq!(value.len(), 3);
+        assert!(!value.is_empty());
+        assert_eq!(
+            value.entries().map(|(_, v)| v.len()).collect::<Vec<_>>(),
+            [3, 0, 0]
+        );
+        assert!(JValue::Array(vec![]).is_empty());
+        assert!(JValue::Null.is_empty());
+        assert_eq!(JValue::Bool(true).len(), 0);
+    }
+
+    #[test]
+    fn test_take() {
+        let mut value = Parser::new("{\"a\": [1, {\"b\": true}], \"c\": 2}".to_string())
+            .parse()
+            .unwrap();
+        let JValue::Object(m) = &mut value else {
+            unreachable!()
+        };
+
+        let a = m["a"].take();
+        assert_eq!(a.to_string(), "[1,{\"b\":true}]");
+        let c = m["c"].replace(JValue::Bool(false));
+        assert_eq!(c.to_string(), "2");
+        assert_eq!(value.to_string(), "{\"a\":null,\"c\":false}");
+    }
+
+    #[test]
+    fn test_get() {
+        let value = Parser::new("{\"a\": [10, 20], \"b\": {\"c\": null}}".to_string())
+            .parse()
+            .unwrap();
+
+        assert_eq!(value.get("b").unwrap().to_string(), "{\"c\":null}");
+        assert_eq!(value.get("x"), None);
+        assert_eq!(value.get_index(1), value.get("b"));
+        assert_eq!(
+            value.get("a").unwrap().get_index(1).unwrap().to_string(),
+            "20"
+        );
+        assert_eq!(value.pointer("/a/0").unwrap().to_string(), "10");
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/a/2"), None);
+        assert_eq!(value.pointer("a"), None);
+    }
+
+    #[test]
+    fn test_pointer_mut() {
+        let mut value = Parser::new("{\"a\": {\"b\": [1, 2]}, \"c\": {\"d\": 1}}".to_string())
+            .parse()
+            .unwrap();
+
+        *value.pointer_mut("/a/b").unwrap() = JValue::String("x".to_string());
+        *value.get_mut("c").unwrap().get_mut("d").unwrap() = JValue::Null;
+        *value.get_index_mut(1).unwrap().get_index_mut(0).unwrap() = JValue::Bool(true);
+
+        assert_eq!(
+            value.to_string(),
+            "{\"a\":{\"b\":\"x\"},\"c\":{\"d\":true}}"
+        );
+        assert_eq!(value.pointer_mut("/a/b/0"), None);
+    }
+
+    #[test]
+    fn test_get_path() {
+        use PathSegment::{Index, Key};
+
+        let value = Parser::new(
+            "{\"users\": [{\"name\": \"ann\", \"tags\": [\"a\", \"b\"]}], \"0\": true}".to_string(),
+        )
+        .parse()
+        .unwrap();
+
+        assert_eq!(
+            value.get_path(&[Key("users"), Index(0), Key("tags"), Index(1)]),
+            Some(&JValue::String("b".to_string()))
+        );
+        assert_eq!(
+            value.get_path(&[Key("users"), Index(0), Key("name")]),
+            value.pointer("/users/0/name")
+        );
+        assert_eq!(value.get_path(&[]), Some(&value));
+        assert_eq!(value.get_path(&[Key("0")]), Some(&JValue::Bool(true)));
+        // segments must match the container
+        assert_eq!(value.get_path(&[Index(0)]), None);
+        assert_eq!(value.get_path(&[Key("users"), Key("0")]), None);
+        assert_eq!(value.get_path(&[Key("users"), Index(1)]), None);
+    }
+
+    #[test]
+    fn test_pointer_or() {
+        let value = Parser::new("{\"db\": {\"port\": 5432, \"host\": null}}".to_string())
+            .parse()
+            .unwrap();
+        let fallback = JValue::String("fallback".to_string());
+
+        assert_eq!(value.pointer_or("/db/port", &fallback).to_string(), "5432");
+        // an explicit null is present, not missing
+        assert_eq!(value.pointer_or("/db/host", &fallback), &JValue::Null);
+        assert_eq!(value.pointer_or("/db/user", &fallback), &fallback);
+        assert_eq!(value.pointer_or("/db/port/0", &fallback), &fallback);
+
+        let db = value.get_or("db", &fallback);
+        assert_eq!(db.get_or("port", &fallback).to_string(), "5432");
+        assert_eq!(db.get_or("user", &fallback), &fallback);
+        assert_eq!(JValue::Null.get_or("db", &fallback), &fallback);
+    }
+
+    #[test]
+    fn test_insert_remove_push() {
+        let mut value = Parser::new("{\"a\": 1, \"b\": [], \"c\": 3}".to_string())
+            .parse()
+            .unwrap();
+
+        assert_eq!(value.insert("a", JValue::Null).unwrap().to_string(), "1");
+        assert_eq!(value.insert("d".to_string(), JValue::Bool(true)), None);
+        assert_eq!(value.remove("b"), Some(JValue::Array(vec![])));
+        assert_eq!(value.remove("b"), None);
+        assert_eq!(value.to_string(), "{\"a\":null,\"c\":3,\"d\":true}");
+
+        let mut arr = JValue::Array(vec![]);
+        arr.push(JValue::Null);
+        assert_eq!(arr.to_string(), "[null]");
+    }
+
+    #[test]
+    fn test_insert_remove_push_on_scalar() {
+        let mut value = JValue::String("s".to_string());
+
+        assert_eq!(value.insert("a", JValue::Null), None);
+        assert_eq!(value.remove("a"), None);
+        value.push(JValue::Null);
+        assert_eq!(value, JValue::String("s".to_string()));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let value =
+            Parser::new("{\"a\": {\"b\": 1, \"c\": {\"d\": null}}, \"e\": \"x\"}".to_string())
+                .parse()
+                .unwrap();
+        let flat = value.flatten();
+
+        assert_eq!(
+            flat.iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>(),
+            ["a.b=1", "a.c.d=null", "e=\"x\""]
+        );
+    }
+
+    #[test]
+    fn test_flatten_arrays() {
+        let value = Parser::new(
+            "{\"a\": [1, [true, {\"b\": 2}]], \"empty\": [], \"none\": {}}".to_string(),
+        )
+        .parse()
+        .unwrap();
+        let flat = value.flatten();
+
+        assert_eq!(
+            flat.keys().collect::<Vec<_>>(),
+            ["a.0", "a.1.0", "a.1.1.b", "empty", "none"]
+        );
+        let arr = JValue::Array(vec![JValue::Bool(true)]);
+        assert_eq!(arr.flatten().keys().collect::<Vec<_>>(), ["0"]);
+        assert_eq!(JValue::Null.flatten().get(""), Some(&JValue::Null));
+    }
+
+    #[test]
+    fn test_flatten_with() {
+        let value =
+            Parser::new("{\"db\": {\"hosts\": [\"a\", \"b\"], \"port\": 5432}}".to_string())
+                .parse()
+                .unwrap();
+        let flat = value.flatten_with('_');
+
+        assert_eq!(
+            flat.keys().collect::<Vec<_>>(),
+            ["db_hosts_0", "db_hosts_1", "db_port"]
+        );
+    }
+
+    #[test]
+    fn test_unflatten() {
+        let value = Parser::new(
+            "{\"a\": {\"b\": 1, \"c\": [true, {\"d\": null}]}, \"e\": \"x\"}".to_string(),
+        )
+        .parse()
+        .unwrap();
+
+        assert_eq!(JValue::unflatten(&value.flatten(), '.'), Ok(value.clone()));
+        assert_eq!(JValue::unflatten(&value.flatten_with('/'), '/'), Ok(value));
+    }
+
+    #[test]
+    fn test_unflatten_keys() {
+        let mut map = IndexMap::new();
+        map.insert("a.b".to_string(), JValue::Null);
+        map.insert("c".to_string(), JValue::Bool(true));
+        map.insert("a.c".to_string(), JValue::Bool(false));
+        // out of order, so kept as an object
+        map.insert("n.1".to_string(), JValue::Null);
+        map.insert("n.0".to_string(), JValue::Null);
+
+        assert_eq!(
+            JValue::unflatten(&map, '.').unwrap().to_string(),
+            "{\"a\":{\"b\":null,\"c\":false},\"c\":true,\"n\":{\"1\":null,\"0\":null}}"
+        );
+        assert_eq!(
+            JValue::unflatten(&IndexMap::new(), '.'),
+            Ok(JValue::Object(IndexMap::new()))
+        );
+    }
+
+    #[test]
+    fn test_unflatten_conflict() {
+        for keys in [["a", "a.b"], ["a.b", "a"], ["x.y.z", "x.y"]] {
+            let map = keys
+                .iter()
+                .map(|k| (k.to_string(), JValue::Null))
+                .collect::<IndexMap<_, _>>();
+
+            assert_eq!(
+                JValue::unflatten(&map, '.'),
+                Err(UnflattenError::Conflict(keys[1].to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_leaf_paths() {
+        let input =
+            "{\"a\": [1, 2], \"b\": {\"c/d\": null, \"e\": [[], {}, [\"x\"]]}, \"f~\": true}";
+        let value = Parser::new(input.to_string()).parse().unwrap();
+
+        assert_eq!(
+            value.leaf_paths(),
+            ["/a/0", "/a/1", "/b/c~1d", "/b/e/0", "/b/e/1", "/b/e/2/0", "/f~0"]
+        );
+        for path in value.leaf_paths() {
+            assert!(value.pointer(&path).is_some_and(JValue::is_empty));
+        }
+        assert_eq!(JValue::Null.leaf_paths(), [""]);
+    }
+}
+
+// empty arrays and objects through every traversal, as the edge most likely
+// to be handled differently by each
+#[cfg(test)]
+mod tests_empty_containers {
+    use super::*;
+
+    fn parse(s: &str) -> JValue {
+        Parser::new(s.to_string()).parse().unwrap()
+    }
+
+    #[test]
+    fn test_walk() {
+        let value = parse("{\"a\": [], \"b\": {}, \"c\": [{}]}");
+        let mut visited = Vec::new();
+        value.walk(&mut |v| visited.push(v.to_string()));
+
+        assert_eq!(
+            visited,
+            [
+                value.to_string(),
+                "[]".into(),
+                "{}".into(),
+                "[{}]".into(),
+                "{}".into()
+            ]
+        );
+
+        let mut empty = Vec::new();
+        parse("[]").walk(&mut |v| empty.push(v.to_string()));
+        assert_eq!(empty, ["[]"]);
+    }
+
+    #[test]
+    fn test_walk_mut() {
+        let mut value = parse("{\"a\": [], \"b\": {\"c\": {}}}");
+        value.walk_mut(&mut |v| {
+            if v.is_empty() && v.is_array() {
+                *v = JValue::Array(vec![JValue::Null]);
+            }
+        });
+
+        assert_eq!(value.to_string(), "{\"a\":[null],\"b\":{\"c\":{}}}");
+    }
+
+    #[test]
+    fn test_flatten() {
+        let value = parse("{\"a\": [], \"b\": {\"c\": {}}, \"d\": [[], 1]}");
+        let flat = value.flatten();
+
+        assert_eq!(
+            flat.iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>(),
+            ["a=[]", "b.c={}", "d.0=[]", "d.1=1"]
+        );
+        assert_eq!(JValue::unflatten(&flat, '.'), Ok(value));
+        assert!(parse("{}").flatten().is_empty());
+        assert!(parse("[]").flatten_with('/').is_empty());
+        assert_eq!(
+            JValue::unflatten(&parse("{}").flatten(), '.'),
+            Ok(parse("{}"))
+        );
+    }
+
+    #[test]
+    fn test_leaf_paths() {
+        let value = parse("{\"a\": [], \"b\": {\"c\": {}}, \"d\": [[], 1]}");
+
+        assert_eq!(value.leaf_paths(), ["/a", "/b/c", "/d/0", "/d/1"]);
+        let flat_keys = value
+            .flatten_with('/')
+            .into_keys()
+            .map(|k| format!("/{}", k));
+        assert_eq!(value.leaf_paths(), flat_keys.collect::<Vec<_>>());
+        assert!(parse("{}").leaf_paths().is_empty());
+        assert!(parse("[]").leaf_paths().is_empty());
+    }
+
+    #[test]
+    fn test_depth() {
+        assert_eq!(JValue::Null.depth(), 0);
+        assert_eq!(parse("[]").depth(), 1);
+        assert_eq!(parse("{}").depth(), 1);
+        assert_eq!(parse("[1, \"x\"]").depth(), 1);
+        assert_eq!(parse("{\"a\": []}").depth(), 2);
+        assert_eq!(parse("[[], [[{}]], 1]").depth(), 4);
+    }
+}